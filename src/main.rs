@@ -1,431 +1,970 @@
-use parse_int::parse;
-use radio_panel::{constants::*, device::*, frequency::*, hardware::*, states::*, utility::*};
-use simconnect::{self, DispatchResult, SimConnector};
+use radio_panel::{
+    acceleration::*, animation::*, assignment::*, device::*, error::*, events::*, frequency::*,
+    gestures::*, hardware::*, pages::*, spacing::*, states::*, transport::*, utility::*,
+};
+#[cfg(windows)]
+use simulator::msfs::SimConnectBackend;
+use simulator::{backend::*, flightgear::*, tracking::ChangeTracker, xplane::*};
 use std::{
-    thread,
-    time::{self},
+    env,
+    net::{SocketAddr, ToSocketAddrs},
+    process,
+    time::{self, Instant},
 };
 
 mod radio_panel;
+#[cfg(test)]
+mod scenario;
+mod simulator;
+
+/// How long after the last panel input values from the simulator are taken over again
+/// Right after sending, the simulator might still report the previous value
+const SIM_SYNC_HOLD_OFF: time::Duration = time::Duration::from_secs(1);
+
+/// How often to try connecting to the simulator while it isn't there
+/// An attempt can block for a while, in between the animation keeps moving
+const SIM_CONNECT_INTERVAL: time::Duration = time::Duration::from_secs(2);
+
+const PASCAL_PER_INHG: f64 = 3_386.39;
+
+/// Autopilot altitudes the five digit window can show, A320's minimum setting is 100
+const AUTOPILOT_ALTITUDE_RANGE: (i32, i32) = (100, 99_999);
+
+/// A single Radio Panel and the instruments it is in charge of
+struct Panel<T: PanelTransport = HidTransport> {
+    radio_panel: RadioPanel<T>,
+    input: InputState,
+    accelerator: RotaryAccelerator,
+    gestures: GestureTracker,
+    assignment: PanelAssignment,
+    pages: PageRegistry,
+}
+
+/// Which flight simulator to talk to
+enum SimulatorChoice {
+    #[cfg(windows)]
+    Msfs,
+    XPlane(SocketAddr),
+    FlightGear(SocketAddr),
+}
+
+/// What can be changed on the command line
+struct Arguments {
+    assignments: Vec<(String, PanelAssignment)>,
+    blink_interval: Option<time::Duration>,
+    adf_half_khz_steps: bool,
+    com_spacing: [ChannelSpacing; 2], // COM1 and COM2
+    vfr_codes: Vec<[i8; 4]>,
+    pages: PageRegistry,
+    simulator: SimulatorChoice,
+}
 
 fn main() {
-    let mut radio_panel = RadioPanel::new();
+    let arguments = match parse_arguments(env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(error) => {
+            println!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let simulator: Box<dyn SimulatorBackend> = match arguments.simulator {
+        #[cfg(windows)]
+        SimulatorChoice::Msfs => Box::new(SimConnectBackend::new()),
+        SimulatorChoice::XPlane(address) => Box::new(XPlaneBackend::new(address)),
+        SimulatorChoice::FlightGear(address) => Box::new(FlightGearBackend::new(address)),
+    };
+    // Only what the panel changes is sent, the virtual cockpit stays usable
+    let mut simulator = ChangeTracker::new(simulator);
+    let result = open_panels(&arguments).and_then(|mut panels| {
+        let mut state = instruments_state(&arguments);
+        run(
+            &mut panels,
+            &mut state,
+            &mut simulator,
+            SIM_SYNC_HOLD_OFF,
+            SIM_CONNECT_INTERVAL,
+            |_, _, _| true,
+        )
+    });
+    if let Err(error) = result {
+        println!("{}", error);
+        process::exit(1);
+    }
+}
+
+/// e.g. "1=com1,com2,nav1,nav2" "2=xpdr,ap" "--blink-interval=250" "--com-spacing=8.33" "--xplane=192.168.0.2"
+/// Panels not mentioned handle everything
+/// Uses FS2020 on Windows and X-Plane on this computer everywhere else by default
+fn parse_arguments(arguments: impl Iterator<Item = String>) -> Result<Arguments, String> {
+    let mut parsed = Arguments {
+        assignments: Vec::new(),
+        blink_interval: None,
+        adf_half_khz_steps: false,
+        com_spacing: [ChannelSpacing::Khz25; 2],
+        vfr_codes: vec![[7, 0, 0, 0]],
+        pages: PageRegistry::default(),
+        #[cfg(windows)]
+        simulator: SimulatorChoice::Msfs,
+        #[cfg(not(windows))]
+        simulator: SimulatorChoice::XPlane(SocketAddr::from(([127, 0, 0, 1], XPLANE_DEFAULT_PORT))),
+    };
+
+    for argument in arguments {
+        if let Some(milliseconds) = argument.strip_prefix("--blink-interval=") {
+            let milliseconds = milliseconds
+                .parse()
+                .map_err(|_| format!("Invalid blink interval: {:?}", milliseconds))?;
+            parsed.blink_interval = Some(time::Duration::from_millis(milliseconds));
+        } else if let Some(spacing) = argument.strip_prefix("--com-spacing=") {
+            parsed.com_spacing = [spacing.parse()?; 2];
+        } else if let Some(spacing) = argument.strip_prefix("--com1-spacing=") {
+            parsed.com_spacing[0] = spacing.parse()?;
+        } else if let Some(spacing) = argument.strip_prefix("--com2-spacing=") {
+            parsed.com_spacing[1] = spacing.parse()?;
+        } else if let Some(codes) = argument.strip_prefix("--vfr-codes=") {
+            parsed.vfr_codes = codes
+                .split(',')
+                .map(parse_xpdr_code)
+                .collect::<Result<_, _>>()?;
+        } else if let Some(pages) = argument.strip_prefix("--pages=") {
+            parsed.pages = pages.parse()?;
+        } else if argument == "--adf-500hz" {
+            parsed.adf_half_khz_steps = true;
+        } else if argument == "--msfs" {
+            #[cfg(windows)]
+            {
+                parsed.simulator = SimulatorChoice::Msfs;
+            }
+            #[cfg(not(windows))]
+            return Err(String::from("FS2020 is only available on Windows"));
+        } else if argument == "--xplane" {
+            parsed.simulator =
+                SimulatorChoice::XPlane(SocketAddr::from(([127, 0, 0, 1], XPLANE_DEFAULT_PORT)));
+        } else if let Some(host) = argument.strip_prefix("--xplane=") {
+            parsed.simulator =
+                SimulatorChoice::XPlane(parse_address(host, XPLANE_DEFAULT_PORT, "X-Plane")?);
+        } else if argument == "--flightgear" {
+            parsed.simulator = SimulatorChoice::FlightGear(SocketAddr::from((
+                [127, 0, 0, 1],
+                FLIGHTGEAR_DEFAULT_PORT,
+            )));
+        } else if let Some(host) = argument.strip_prefix("--flightgear=") {
+            parsed.simulator = SimulatorChoice::FlightGear(parse_address(
+                host,
+                FLIGHTGEAR_DEFAULT_PORT,
+                "FlightGear",
+            )?);
+        } else {
+            parsed.assignments.push(parse_panel_argument(&argument)?);
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Host name or IP address, with an optional port
+fn parse_address(host: &str, default_port: u16, simulator: &str) -> Result<SocketAddr, String> {
+    let with_port = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, default_port)
+    };
+    with_port
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| format!("Invalid {} address: {:?}", simulator, host))
+}
+
+/// The instruments as set on the command line, everything else at its default
+fn instruments_state(arguments: &Arguments) -> InstrumentStates {
     let mut state = instruments_default_state();
-    let mut simulator = simconnect::SimConnector::new();
+    state.adf_state.half_khz_steps = arguments.adf_half_khz_steps;
+    state.com1_state.channel_spacing = arguments.com_spacing[0];
+    state.com2_state.channel_spacing = arguments.com_spacing[1];
+    state.xpdr_state.quick_codes = arguments.vfr_codes.clone();
+    state
+}
+
+/// Polls the panels in turn and keeps them and the simulator in sync
+/// After every pass over the panels, between_passes gets to look at them and the simulator,
+/// returning false stops the loop
+/// Values from the simulator are taken over once no panel was touched for sync_hold_off
+fn run<T: PanelTransport, S: SimulatorBackend>(
+    panels: &mut [Panel<T>],
+    state: &mut InstrumentStates,
+    simulator: &mut S,
+    sync_hold_off: time::Duration,
+    connect_interval: time::Duration,
+    mut between_passes: impl FnMut(&mut [Panel<T>], &mut S, bool) -> bool,
+) -> Result<(), RadioPanelError> {
     let mut connected_to_sim = false;
+    let mut last_panel_input = Instant::now();
+    let mut last_connect_attempt: Option<Instant> = None;
+    let mut show_connecting = true; // once per lost connection, touching the panel stops it
 
-    let mut input = InputState::new();
     loop {
         if connected_to_sim {
-            input = match radio_panel.block_until_input() {
-                Some(updated_input_state) => updated_input_state,
-                None => input,
-            };
-
-            if input.mode_selector_upper == input.mode_selector_lower {
-                apply_autopilot_input(
-                    &mut state.autopilot_state,
-                    input.rotary_upper_outer,
-                    input.rotary_upper_inner,
-                    input.rotary_lower_outer,
-                    input.rotary_lower_inner,
-                    input.button_lower,
-                );
-                autopilot_logic(&state.autopilot_state, &simulator, &mut radio_panel);
-                continue;
+            for panel in panels.iter_mut() {
+                let events = match panel.radio_panel.block_until_events() {
+                    Ok(events) => events,
+                    Err(error) => {
+                        log_recoverable(error)?;
+                        Vec::new()
+                    }
+                };
+                let events = panel.gestures.track(events, Instant::now());
+                if !events.is_empty() {
+                    last_panel_input = Instant::now();
+                } else if last_panel_input.elapsed() >= sync_hold_off {
+                    // e.g. changed in the virtual cockpit
+                    sync_states_from_sim(state, simulator);
+                }
+                if let Err(error) =
+                    handle_events(panel, events, state, &mut connected_to_sim, simulator)
+                {
+                    log_recoverable(error)?;
+                }
             }
 
-            handle_upper_panel(
-                input,
-                &mut state,
-                &mut radio_panel,
-                &mut connected_to_sim,
-                &simulator,
-            );
-            handle_lower_panel(
-                input,
-                &mut state,
-                &mut radio_panel,
-                &mut connected_to_sim,
-                &simulator,
-            );
+            if !connected_to_sim {
+                show_connecting = true;
+                println!("Lost connection to simulator");
+                for panel in panels.iter() {
+                    let stats = panel.radio_panel.display_stats();
+                    println!(
+                        "Display updates sent: {}, skipped as unchanged: {}",
+                        stats.reports_sent, stats.reports_skipped
+                    );
+                }
+            }
         } else {
-            loop {
-                if simulator.connect("BetterRadioPanel") {
-                    setup_simulator_event_ids(&mut simulator);
-                    connected_to_sim = true;
-                    show_standby_screen(&mut radio_panel);
-                    break;
-                } else {
-                    show_connecting_animation(&mut radio_panel)
+            if show_connecting {
+                show_connecting = false;
+                for panel in panels.iter_mut() {
+                    if let Err(error) = show_connecting_animation(&mut panel.radio_panel) {
+                        log_recoverable(error)?;
+                    }
                 }
             }
+
+            let connect_due = last_connect_attempt.is_none_or(|last_connect_attempt| {
+                last_connect_attempt.elapsed() >= connect_interval
+            });
+            if connect_due {
+                last_connect_attempt = Some(Instant::now());
+            }
+            if connect_due && simulator.connect() {
+                connected_to_sim = true;
+                // Start out with what the aircraft is tuned to, e.g. loaded with the flight plan
+                sync_states_from_sim(state, simulator);
+                for panel in panels.iter_mut() {
+                    panel.radio_panel.cancel_all_animations();
+                    if let Err(error) = show_standby_screen(&mut panel.radio_panel) {
+                        log_recoverable(error)?;
+                    }
+                }
+            } else {
+                for panel in panels.iter_mut() {
+                    // Keeps the animation moving and the mode selector positions known
+                    match panel.radio_panel.block_until_events() {
+                        Ok(events) => {
+                            for event in events {
+                                panel.input = panel.input.with_event(event);
+                            }
+                        }
+                        Err(error) => log_recoverable(error)?,
+                    }
+                }
+            }
+        }
+
+        if !between_passes(panels, simulator, connected_to_sim) {
+            return Ok(());
+        }
+    }
+}
+
+/// Errors worth a message, not the whole program, are only printed, the others returned
+/// e.g. a garbled input report or a failed USB transfer, the next poll is likely fine again,
+/// or a value the windows can't show
+fn log_recoverable(error: RadioPanelError) -> Result<(), RadioPanelError> {
+    match error {
+        RadioPanelError::MalformedReport(_)
+        | RadioPanelError::Io(_)
+        | RadioPanelError::InvalidGlyph(_)
+        | RadioPanelError::ValueTooLong(_) => {
+            println!("{}", error);
+            Ok(())
+        }
+        error => Err(error),
+    }
+}
+
+/// Open every Radio Panel plugged in, each with the assignment given on the command line
+/// Panels are referred to by serial number or by their 1-based position in the list
+fn open_panels(arguments: &Arguments) -> Result<Vec<Panel>, RadioPanelError> {
+    let assignments = &arguments.assignments;
+    let hid_panels = HidPanels::new()?;
+    let panel_infos = hid_panels.list()?;
+    if panel_infos.is_empty() {
+        return Err(RadioPanelError::NotFound);
+    }
+
+    let mut panels = Vec::new();
+    for (index, panel_info) in panel_infos.iter().enumerate() {
+        let position = (index + 1).to_string();
+        let assignment = assignments
+            .iter()
+            .find(|(panel, _)| {
+                *panel == position || Some(panel) == panel_info.serial_number.as_ref()
+            })
+            .map_or_else(PanelAssignment::all, |(_, assignment)| assignment.clone());
+
+        println!(
+            "Radio panel {} (serial number {}): {:?}",
+            position,
+            panel_info.serial_number.as_deref().unwrap_or("unknown"),
+            assignment
+        );
+
+        let mut radio_panel = RadioPanel::open(&hid_panels, panel_info)?;
+        // All panels are polled in turn, keep the total response time the same
+        radio_panel.set_input_timeout(300 / panel_infos.len() as i32);
+        if let Some(blink_interval) = arguments.blink_interval {
+            radio_panel.set_blink_interval(blink_interval);
         }
+
+        panels.push(Panel {
+            radio_panel,
+            input: InputState::new(),
+            accelerator: RotaryAccelerator::default(),
+            gestures: GestureTracker::default(),
+            assignment,
+            pages: arguments.pages.clone(),
+        });
+    }
+
+    for (panel, _) in assignments {
+        let found = panel_infos.iter().enumerate().any(|(index, panel_info)| {
+            *panel == (index + 1).to_string() || Some(panel) == panel_info.serial_number.as_ref()
+        });
+        if !found {
+            println!("No radio panel {} found, ignoring its assignment", panel);
+        }
+    }
+
+    Ok(panels)
+}
+
+/// Act on everything that happened on a panel since it was last polled
+fn handle_events(
+    panel: &mut Panel<impl PanelTransport>,
+    events: Vec<InputEvent>,
+    state: &mut InstrumentStates,
+    connected_to_sim: &mut bool,
+    simulator: &mut dyn SimulatorBackend,
+) -> Result<(), RadioPanelError> {
+    panel.input = panel.input.idle();
+
+    if events.is_empty() {
+        // Nothing touched, still keep displays up to date and notice a lost simulator
+        handle_input(
+            panel.input,
+            &panel.assignment,
+            &panel.pages,
+            state,
+            &mut panel.radio_panel,
+            connected_to_sim,
+            simulator,
+        )?;
+    }
+
+    for event in events {
+        panel.input = panel.input.with_event(event);
+        if let InputEvent::RotaryTurned { knob, direction } = event {
+            let mode = match knob {
+                Knob::UpperInner | Knob::UpperOuter => panel.input.mode_selector_upper,
+                Knob::LowerInner | Knob::LowerOuter => panel.input.mode_selector_lower,
+            };
+            let page = shown_page(panel.input, &panel.assignment, &panel.pages);
+            panel.input.rotary_steps =
+                panel
+                    .accelerator
+                    .steps(knob, direction, mode, page, Instant::now());
+        }
+        handle_input(
+            panel.input,
+            &panel.assignment,
+            &panel.pages,
+            state,
+            &mut panel.radio_panel,
+            connected_to_sim,
+            simulator,
+        )?;
+    }
+    Ok(())
+}
+
+/// The page the windows show, if both mode selectors are in the same position
+fn shown_page(
+    input: InputState,
+    assignment: &PanelAssignment,
+    pages: &PageRegistry,
+) -> Option<Page> {
+    (assignment.autopilot && input.mode_selector_upper == input.mode_selector_lower)
+        .then(|| pages.page(input.mode_selector_upper))
+}
+
+fn handle_input(
+    input: InputState,
+    assignment: &PanelAssignment,
+    pages: &PageRegistry,
+    state: &mut InstrumentStates,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+    connected_to_sim: &mut bool,
+    simulator: &mut dyn SimulatorBackend,
+) -> Result<(), RadioPanelError> {
+    if let Some(page) = shown_page(input, assignment, pages) {
+        handle_page(page, input, state, radio_panel, simulator)?;
+        // pages only send what changed, so ask whether the simulator is still there
+        *connected_to_sim = simulator.is_connected();
+        return Ok(());
+    }
+
+    if assignment.handles(input.mode_selector_upper) {
+        handle_upper_panel(input, state, radio_panel, connected_to_sim, simulator)?;
+    } else {
+        display_unassigned_on_hardware(radio_panel, Window::TopLeft, Window::TopRight)?;
+    }
+
+    if assignment.handles(input.mode_selector_lower) {
+        handle_lower_panel(input, state, radio_panel, connected_to_sim, simulator)
+    } else {
+        display_unassigned_on_hardware(radio_panel, Window::BottomLeft, Window::BottomRight)
     }
 }
 
 fn handle_upper_panel(
     input: InputState,
     state: &mut InstrumentStates,
-    radio_panel: &mut RadioPanel,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
     connected_to_sim: &mut bool,
-    simulator: &SimConnector,
-) {
+    simulator: &mut dyn SimulatorBackend,
+) -> Result<(), RadioPanelError> {
     match input.mode_selector_upper {
         ModeSelectorState::ModeSelectorCom1 => {
-            apply_com_input(
+            let before = frequency_events(&state.com1_state, Radio::Com1);
+            apply_frequency_input(
                 &mut state.com1_state,
                 input.button_upper,
                 input.rotary_upper_outer,
                 input.rotary_upper_inner,
+                input.rotary_steps,
             );
             display_frequency_on_hardware(
                 radio_panel,
                 &state.com1_state,
                 Window::TopLeft,
                 Window::TopRight,
-                3,
-            );
-            *connected_to_sim = send_com_to_sim(
-                &mut state.com1_state,
+            )?;
+            *connected_to_sim = send_frequency_to_sim(
+                &state.com1_state,
+                &before,
                 simulator,
-                EVENT_ID_COM_RADIO_SET_HZ,
-                EVENT_ID_COM_STBY_RADIO_SET_HZ,
+                Radio::Com1,
+                matches!(input.button_upper, ButtonState::Pressed),
             );
         }
         ModeSelectorState::ModeSelectorCom2 => {
-            apply_com_input(
+            let before = frequency_events(&state.com2_state, Radio::Com2);
+            apply_frequency_input(
                 &mut state.com2_state,
                 input.button_upper,
                 input.rotary_upper_outer,
                 input.rotary_upper_inner,
+                input.rotary_steps,
             );
             display_frequency_on_hardware(
                 radio_panel,
                 &state.com2_state,
                 Window::TopLeft,
                 Window::TopRight,
-                3,
-            );
-            *connected_to_sim = send_com_to_sim(
-                &mut state.com2_state,
+            )?;
+            *connected_to_sim = send_frequency_to_sim(
+                &state.com2_state,
+                &before,
                 simulator,
-                EVENT_ID_COM2_RADIO_SET_HZ,
-                EVENT_ID_COM2_STBY_RADIO_SET_HZ,
+                Radio::Com2,
+                matches!(input.button_upper, ButtonState::Pressed),
             );
         }
         ModeSelectorState::ModeSelectorNav1 => {
-            apply_nav_input(
+            let before = nav_events(&state.nav1_state, &state.nav1_course, Radio::Nav1);
+            let swapped = apply_nav_input(
                 &mut state.nav1_state,
+                &mut state.nav1_course,
                 input.button_upper,
                 input.rotary_upper_outer,
                 input.rotary_upper_inner,
+                input.rotary_steps,
+                Instant::now(),
             );
-            display_frequency_on_hardware(
+            display_nav_on_hardware(
                 radio_panel,
                 &state.nav1_state,
+                &state.nav1_course,
                 Window::TopLeft,
                 Window::TopRight,
-                2,
-            );
+            )?;
             *connected_to_sim = send_nav_to_sim(
-                &mut state.nav1_state,
+                &state.nav1_state,
+                &state.nav1_course,
+                &before,
                 simulator,
-                EVENT_ID_NAV1_RADIO_SET_HZ,
-                EVENT_ID_NAV1_STBY_SET_HZ,
+                Radio::Nav1,
+                swapped,
             );
         }
         ModeSelectorState::ModeSelectorNav2 => {
-            apply_nav_input(
+            let before = nav_events(&state.nav2_state, &state.nav2_course, Radio::Nav2);
+            let swapped = apply_nav_input(
                 &mut state.nav2_state,
+                &mut state.nav2_course,
                 input.button_upper,
                 input.rotary_upper_outer,
                 input.rotary_upper_inner,
+                input.rotary_steps,
+                Instant::now(),
             );
-            display_frequency_on_hardware(
+            display_nav_on_hardware(
                 radio_panel,
                 &state.nav2_state,
+                &state.nav2_course,
                 Window::TopLeft,
                 Window::TopRight,
-                2,
-            );
+            )?;
             *connected_to_sim = send_nav_to_sim(
-                &mut state.nav2_state,
+                &state.nav2_state,
+                &state.nav2_course,
+                &before,
                 simulator,
-                EVENT_ID_NAV2_RADIO_SET_HZ,
-                EVENT_ID_NAV2_STBY_SET_HZ,
+                Radio::Nav2,
+                swapped,
             );
         }
         ModeSelectorState::ModeSelectorAdf => {
+            let before = adf_events(&state.adf_state);
+            apply_adf_input(
+                &mut state.adf_state,
+                input.button_upper,
+                input.rotary_upper_outer,
+                input.rotary_upper_inner,
+                input.rotary_steps,
+            );
             display_adf_on_hardware(
                 &state.adf_state,
                 Window::TopLeft,
                 Window::TopRight,
                 radio_panel,
+            )?;
+            *connected_to_sim = send_adf_to_sim(
+                &state.adf_state,
+                &before,
+                simulator,
+                matches!(input.button_upper, ButtonState::Pressed),
             );
         }
         ModeSelectorState::ModeSelectorDme => {
-            state.dme_state.distance = read_dme_from_sim(simulator);
+            state.dme_state.distance = simulator.read_variable(SimVariable::DmeDistance);
             display_dme_on_hardware(
                 radio_panel,
                 &state.dme_state,
                 &state.nav1_state,
                 Window::TopLeft,
                 Window::TopRight,
-            );
+            )?;
         }
         ModeSelectorState::ModeSelectorXpdr => {
+            let before = xpdr_events(&state.xpdr_state);
             apply_xpdr_input(
                 &mut state.xpdr_state,
                 input.button_upper,
                 input.rotary_upper_outer,
                 input.rotary_upper_inner,
+                Instant::now(),
             );
             display_xpdr_on_hardware(
                 radio_panel,
                 &state.xpdr_state,
                 Window::TopLeft,
                 Window::TopRight,
-            );
+            )?;
             *connected_to_sim = send_xpdr_to_sim(
-                &mut state.xpdr_state,
+                &state.xpdr_state,
+                &before,
                 simulator,
-                EVENT_ID_XPNDR_SET,
+                matches!(input.button_upper, ButtonState::Held),
             );
         }
     }
+    Ok(())
 }
 
 fn handle_lower_panel(
     input: InputState,
     state: &mut InstrumentStates,
-    radio_panel: &mut RadioPanel,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
     connected_to_sim: &mut bool,
-    simulator: &SimConnector,
-) {
+    simulator: &mut dyn SimulatorBackend,
+) -> Result<(), RadioPanelError> {
     match input.mode_selector_lower {
         ModeSelectorState::ModeSelectorCom1 => {
-            apply_com_input(
+            let before = frequency_events(&state.com1_state, Radio::Com1);
+            apply_frequency_input(
                 &mut state.com1_state,
                 input.button_lower,
                 input.rotary_lower_outer,
                 input.rotary_lower_inner,
+                input.rotary_steps,
             );
             display_frequency_on_hardware(
                 radio_panel,
                 &state.com1_state,
                 Window::BottomLeft,
                 Window::BottomRight,
-                3,
-            );
-            *connected_to_sim = send_com_to_sim(
-                &mut state.com1_state,
+            )?;
+            *connected_to_sim = send_frequency_to_sim(
+                &state.com1_state,
+                &before,
                 simulator,
-                EVENT_ID_COM_RADIO_SET_HZ,
-                EVENT_ID_COM_STBY_RADIO_SET_HZ,
+                Radio::Com1,
+                matches!(input.button_lower, ButtonState::Pressed),
             );
         }
         ModeSelectorState::ModeSelectorCom2 => {
-            apply_com_input(
+            let before = frequency_events(&state.com2_state, Radio::Com2);
+            apply_frequency_input(
                 &mut state.com2_state,
                 input.button_lower,
                 input.rotary_lower_outer,
                 input.rotary_lower_inner,
+                input.rotary_steps,
             );
             display_frequency_on_hardware(
                 radio_panel,
                 &state.com2_state,
                 Window::BottomLeft,
                 Window::BottomRight,
-                3,
-            );
-            *connected_to_sim = send_com_to_sim(
-                &mut state.com2_state,
+            )?;
+            *connected_to_sim = send_frequency_to_sim(
+                &state.com2_state,
+                &before,
                 simulator,
-                EVENT_ID_COM2_RADIO_SET_HZ,
-                EVENT_ID_COM2_STBY_RADIO_SET_HZ,
+                Radio::Com2,
+                matches!(input.button_lower, ButtonState::Pressed),
             );
         }
         ModeSelectorState::ModeSelectorNav1 => {
-            apply_nav_input(
+            let before = nav_events(&state.nav1_state, &state.nav1_course, Radio::Nav1);
+            let swapped = apply_nav_input(
                 &mut state.nav1_state,
+                &mut state.nav1_course,
                 input.button_lower,
                 input.rotary_lower_outer,
                 input.rotary_lower_inner,
+                input.rotary_steps,
+                Instant::now(),
             );
-            display_frequency_on_hardware(
+            display_nav_on_hardware(
                 radio_panel,
                 &state.nav1_state,
+                &state.nav1_course,
                 Window::BottomLeft,
                 Window::BottomRight,
-                2,
-            );
+            )?;
             *connected_to_sim = send_nav_to_sim(
-                &mut state.nav1_state,
+                &state.nav1_state,
+                &state.nav1_course,
+                &before,
                 simulator,
-                EVENT_ID_NAV1_RADIO_SET_HZ,
-                EVENT_ID_NAV1_STBY_SET_HZ,
+                Radio::Nav1,
+                swapped,
             );
         }
         ModeSelectorState::ModeSelectorNav2 => {
-            apply_nav_input(
+            let before = nav_events(&state.nav2_state, &state.nav2_course, Radio::Nav2);
+            let swapped = apply_nav_input(
                 &mut state.nav2_state,
+                &mut state.nav2_course,
                 input.button_lower,
                 input.rotary_lower_outer,
                 input.rotary_lower_inner,
+                input.rotary_steps,
+                Instant::now(),
             );
-            display_frequency_on_hardware(
+            display_nav_on_hardware(
                 radio_panel,
                 &state.nav2_state,
+                &state.nav2_course,
                 Window::BottomLeft,
                 Window::BottomRight,
-                2,
-            );
+            )?;
             *connected_to_sim = send_nav_to_sim(
-                &mut state.nav2_state,
+                &state.nav2_state,
+                &state.nav2_course,
+                &before,
                 simulator,
-                EVENT_ID_NAV2_RADIO_SET_HZ,
-                EVENT_ID_NAV2_STBY_SET_HZ,
+                Radio::Nav2,
+                swapped,
             );
         }
         ModeSelectorState::ModeSelectorAdf => {
+            let before = adf_events(&state.adf_state);
+            apply_adf_input(
+                &mut state.adf_state,
+                input.button_lower,
+                input.rotary_lower_outer,
+                input.rotary_lower_inner,
+                input.rotary_steps,
+            );
             display_adf_on_hardware(
                 &state.adf_state,
                 Window::BottomLeft,
                 Window::BottomRight,
                 radio_panel,
+            )?;
+            *connected_to_sim = send_adf_to_sim(
+                &state.adf_state,
+                &before,
+                simulator,
+                matches!(input.button_lower, ButtonState::Pressed),
             );
         }
         ModeSelectorState::ModeSelectorDme => {
-            state.dme_state.distance = read_dme_from_sim(simulator);
+            state.dme_state.distance = simulator.read_variable(SimVariable::DmeDistance);
             display_dme_on_hardware(
                 radio_panel,
                 &state.dme_state,
                 &state.nav1_state,
                 Window::BottomLeft,
                 Window::BottomRight,
-            );
+            )?;
         }
         ModeSelectorState::ModeSelectorXpdr => {
+            let before = xpdr_events(&state.xpdr_state);
             apply_xpdr_input(
                 &mut state.xpdr_state,
                 input.button_lower,
                 input.rotary_lower_outer,
                 input.rotary_lower_inner,
+                Instant::now(),
             );
             display_xpdr_on_hardware(
                 radio_panel,
                 &state.xpdr_state,
                 Window::BottomLeft,
                 Window::BottomRight,
-            );
+            )?;
             *connected_to_sim = send_xpdr_to_sim(
-                &mut state.xpdr_state,
+                &state.xpdr_state,
+                &before,
                 simulator,
-                EVENT_ID_XPNDR_SET,
+                matches!(input.button_lower, ButtonState::Held),
             );
         }
     }
+    Ok(())
+}
+
+/// Take over the values the simulator currently has
+/// Values the simulator doesn't report stay as they are
+fn sync_states_from_sim(state: &mut InstrumentStates, simulator: &mut dyn SimulatorBackend) {
+    for (radio, frequency_state) in [
+        (Radio::Com1, &mut state.com1_state),
+        (Radio::Com2, &mut state.com2_state),
+        (Radio::Nav1, &mut state.nav1_state),
+        (Radio::Nav2, &mut state.nav2_state),
+    ] {
+        let spacing = frequency_state.channel_spacing;
+        if let Some(hz) = read_positive(simulator, SimVariable::ActiveFrequency(radio)) {
+            sync_frequency(&mut frequency_state.active_freq, spacing, hz);
+        }
+        if let Some(hz) = read_positive(simulator, SimVariable::StandbyFrequency(radio)) {
+            sync_frequency(&mut frequency_state.standby_freq, spacing, hz);
+        }
+    }
+
+    if let Some(hz) = read_positive(simulator, SimVariable::ActiveFrequency(Radio::Adf)) {
+        state.adf_state.active_frequency = Frequency::closest(Band::Adf, hz.round() as u32);
+    }
+    if let Some(hz) = read_positive(simulator, SimVariable::StandbyFrequency(Radio::Adf)) {
+        state.adf_state.standby_frequency = Frequency::closest(Band::Adf, hz.round() as u32);
+    }
+
+    if let Some(code) = simulator.read_variable(SimVariable::TransponderCode) {
+        let code = code as u16;
+        for (index, digit) in state.xpdr_state.code.iter_mut().enumerate() {
+            *digit = (code / 10_u16.pow(3 - index as u32) % 10) as i8;
+        }
+    }
+
+    if let Some(mode) = simulator
+        .read_variable(SimVariable::TransponderMode)
+        .and_then(TransponderMode::from_value)
+    {
+        state.xpdr_state.mode = mode;
+    }
+
+    let autopilot = &mut state.autopilot_state;
+    let read_autopilot = |simulator: &mut dyn SimulatorBackend, target| {
+        simulator
+            .read_variable(SimVariable::AutopilotValue(target))
+            .map(f64::round)
+    };
+    if let Some(heading) = read_autopilot(simulator, AutopilotTarget::Heading) {
+        autopilot.heading = wrap(heading as i16, 0, 360);
+    }
+    if let Some(altitude) = read_autopilot(simulator, AutopilotTarget::Altitude) {
+        let (lowest, highest) = AUTOPILOT_ALTITUDE_RANGE;
+        autopilot.altitude = (altitude as i32).clamp(lowest, highest);
+    }
+    if let Some(vertical_speed) = read_autopilot(simulator, AutopilotTarget::VerticalSpeed) {
+        autopilot.vertical_speed = vertical_speed as i16;
+    }
+    if let Some(airspeed) = read_autopilot(simulator, AutopilotTarget::Airspeed) {
+        autopilot.airspeed = airspeed as i16;
+    }
+    if let Some(mach) = read_autopilot(simulator, AutopilotTarget::Mach) {
+        autopilot.mach = mach as i16;
+    }
+    if let Some(angle) = read_autopilot(simulator, AutopilotTarget::FlightPathAngle) {
+        autopilot.flight_path_angle = angle as i16;
+    }
+    for mode in AutopilotMode::ALL {
+        match simulator.read_variable(SimVariable::AutopilotEngaged(mode)) {
+            Some(engaged) if engaged != 0.0 => autopilot.engaged.insert(mode),
+            Some(_) => autopilot.engaged.remove(&mode),
+            None => false,
+        };
+    }
+
+    for (radio, course) in [
+        (Radio::Nav1, &mut state.nav1_course),
+        (Radio::Nav2, &mut state.nav2_course),
+    ] {
+        if let Some(degrees) = simulator.read_variable(SimVariable::Course(radio)) {
+            course.degrees = wrap(degrees.round() as i16, 0, 360);
+        }
+    }
+    if let Some(pascal) = read_positive(simulator, SimVariable::Barometer) {
+        state.barometer_state.pascal = pascal.round() as i32;
+    }
+}
+
+/// Keeps the channel name if it is still the same frequency, e.g. 118.005 and 118.000
+fn sync_frequency(frequency: &mut Frequency, spacing: ChannelSpacing, hz: f64) {
+    if (f64::from(spacing.hz(*frequency)) - hz).abs() >= 500.0 {
+        *frequency = spacing.frequency_from_hz(hz.round() as u32);
+    }
+}
+
+/// A frequency of 0 means the aircraft doesn't have that radio
+fn read_positive(simulator: &mut dyn SimulatorBackend, variable: SimVariable) -> Option<f64> {
+    simulator
+        .read_variable(variable)
+        .filter(|value| *value > 0.0)
 }
 
 fn display_frequency_on_hardware(
-    radio_panel: &mut RadioPanel,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
     frequency_state: &FrequencyState,
     left_window: Window,
     right_window: Window,
-    fractional_digits: u8,
-) {
-    radio_panel.set_window(
-        left_window,
-        &format_frequency(frequency_state.active_freq, fractional_digits),
-    );
+) -> Result<(), RadioPanelError> {
+    radio_panel.set_window(left_window, &format_frequency(frequency_state.active_freq))?;
     radio_panel.set_window(
         right_window,
-        &format_frequency(frequency_state.standby_freq, fractional_digits),
-    );
-    radio_panel.update_all_windows();
+        &format_frequency(frequency_state.standby_freq),
+    )?;
+    radio_panel.update_all_windows()
+}
+
+/// With the knobs on the course, it takes the place of the standby frequency
+fn display_nav_on_hardware(
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+    frequency_state: &FrequencyState,
+    course: &CourseState,
+    left_window: Window,
+    right_window: Window,
+) -> Result<(), RadioPanelError> {
+    if !course.obs_mode {
+        return display_frequency_on_hardware(
+            radio_panel,
+            frequency_state,
+            left_window,
+            right_window,
+        );
+    }
+    radio_panel.set_window(left_window, &format_frequency(frequency_state.active_freq))?;
+    radio_panel.set_window(right_window, &format!("  {:0>3}", course.degrees))?;
+    radio_panel.update_all_windows()
 }
 
 fn display_xpdr_on_hardware(
-    radio_panel: &mut RadioPanel,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
     state: &XpdrState,
     left_window: Window,
     right_window: Window,
-) {
-    let code = state.code.map(|d| d.to_string()).join("");
-    let code = format!(" {}", code);
-    let code: String = code
-        .chars()
-        .enumerate()
-        .map(|(i, char)| {
-            if i == (state.selected_digit + 1) {
-                format!("{}.", char) // if digit selected, add dot to it
-            } else {
-                char.to_string()
-            }
-        })
-        .collect();
+) -> Result<(), RadioPanelError> {
+    let code = state.pending_code.unwrap_or(state.code);
+    let code = format!(" {}", code.map(|d| d.to_string()).join(""));
+    // Letters like F, N or T can't be shown, the switch position instead: 0 OFF to 3 ALT
+    let mode = TransponderMode::ALL
+        .iter()
+        .position(|mode| *mode == state.mode)
+        .unwrap();
 
-    radio_panel.set_window(left_window, "     ");
-    radio_panel.set_window(right_window, &code);
-    radio_panel.update_all_windows();
+    radio_panel.set_window(left_window, &format!("{:>5}", mode))?;
+    radio_panel.set_window(right_window, &code)?;
+    radio_panel.set_display_blinking(right_window, state.selected_digit + 1, true); // the digit being edited
+    radio_panel.update_all_windows()
 }
 
-fn setup_simulator_event_ids(simulator: &mut SimConnector) {
-    // Tell simulator which event ID is supposed to represent what simulator event
-    simulator.map_client_event_to_sim_event(EVENT_ID_COM_RADIO_SET_HZ, "COM_RADIO_SET_HZ");
-    simulator
-        .map_client_event_to_sim_event(EVENT_ID_COM_STBY_RADIO_SET_HZ, "COM_STBY_RADIO_SET_HZ");
-    simulator.map_client_event_to_sim_event(EVENT_ID_COM2_RADIO_SET_HZ, "COM2_RADIO_SET_HZ");
-    simulator
-        .map_client_event_to_sim_event(EVENT_ID_COM2_STBY_RADIO_SET_HZ, "COM2_STBY_RADIO_SET_HZ");
-    simulator.map_client_event_to_sim_event(EVENT_ID_NAV1_RADIO_SET_HZ, "NAV1_RADIO_SET_HZ");
-    simulator.map_client_event_to_sim_event(EVENT_ID_NAV1_STBY_SET_HZ, "NAV1_STBY_SET_HZ");
-    simulator.map_client_event_to_sim_event(EVENT_ID_NAV2_RADIO_SET_HZ, "NAV2_RADIO_SET_HZ");
-    simulator.map_client_event_to_sim_event(EVENT_ID_NAV2_STBY_SET_HZ, "NAV2_STBY_SET_HZ");
-    simulator.map_client_event_to_sim_event(EVENT_ID_XPNDR_SET, "XPNDR_SET");
-
-    simulator.map_client_event_to_sim_event(EVENT_ID_HEADING_BUG_SET, "HEADING_BUG_SET");
-    simulator
-        .map_client_event_to_sim_event(EVENT_ID_AP_ALT_VAR_SET_ENGLISH, "AP_ALT_VAR_SET_ENGLISH");
-    simulator
-        .map_client_event_to_sim_event(EVENT_ID_AP_VS_VAR_SET_ENGLISH, "AP_VS_VAR_SET_ENGLISH");
-    simulator.map_client_event_to_sim_event(EVENT_ID_AP_SPD_VAR_SET, "AP_SPD_VAR_SET");
+/// Another panel is in charge of this mode, keep the windows dark
+fn display_unassigned_on_hardware(
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+    left_window: Window,
+    right_window: Window,
+) -> Result<(), RadioPanelError> {
+    radio_panel.set_window(left_window, "     ")?;
+    radio_panel.set_window(right_window, "     ")?;
+    radio_panel.update_all_windows()
 }
 
-fn show_connecting_animation(radio_panel: &mut RadioPanel) {
-    radio_panel.clear_all_windows();
-
-    for window_index in 0..4 {
-        let window = match window_index {
-            0 => Window::TopLeft,
-            1 => Window::TopRight,
-            2 => Window::BottomLeft,
-            3 => Window::BottomRight,
-            _ => Window::TopLeft,
-        };
-
-        for character_index in 0..5 {
-            let mut content = String::from("     ");
-            content.replace_range(character_index..character_index + 1, "-");
-
-            radio_panel.set_window(window, &content);
-            radio_panel.update_all_windows();
-
-            thread::sleep(time::Duration::from_millis(300));
-        }
-
-        radio_panel.set_window(window, "     ")
-    }
+/// Keeps moving while waiting for the simulator, shows the version on the bottom right
+fn show_connecting_animation(
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+) -> Result<(), RadioPanelError> {
+    radio_panel.clear_all_windows()?;
+    radio_panel.start_animation(Window::TopLeft, &Animation::Sweep)?;
+    radio_panel.start_animation(Window::TopRight, &Animation::Sweep)?;
+    radio_panel.start_animation(Window::BottomLeft, &Animation::Spinner)?;
+    radio_panel.start_animation(
+        Window::BottomRight,
+        &Animation::Marquee(env!("CARGO_PKG_VERSION").to_string()),
+    )?;
+    radio_panel.update_all_windows()
 }
 
 fn display_dme_on_hardware(
-    radio_panel: &mut RadioPanel,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
     dme_state: &DmeState,
     nav1_state: &FrequencyState,
     window_active: Window,
     window_standby: Window,
-) {
+) -> Result<(), RadioPanelError> {
     let formatted_distance: String = match dme_state.distance {
         Some(distance) => {
             let formatted_distance = format!("{:.1}", distance);
@@ -434,250 +973,814 @@ fn display_dme_on_hardware(
         }
         None => "    -".to_string(),
     };
-    radio_panel.set_window(window_active, &format_frequency(nav1_state.active_freq, 2));
-    radio_panel.set_window(window_standby, &formatted_distance);
-    radio_panel.update_all_windows();
-}
-
-fn autopilot_logic(state: &AutopilotState, simulator: &SimConnector, radio_panel: &mut RadioPanel) {
-    simulator.transmit_client_event(1, 1009, state.heading as u32, 5, 0);
-    simulator.transmit_client_event(1, 1010, state.altitude as u32, 5, 0);
-    simulator.transmit_client_event(1, 1011, state.vertical_speed as u32, 5, 0);
-    simulator.transmit_client_event(1, 1012, state.airspeed as u32, 5, 0);
-    radio_panel.set_window(Window::TopLeft, &format!("{:>5}", state.airspeed));
-    radio_panel.set_window(Window::TopRight, &format!("  {:0>3}", state.heading));
-
-    let selected_indicator_altitude = match state.selected_setting {
-        AutopilotValue::Altitude => ".",
-        AutopilotValue::VerticalSpeed => "",
+    radio_panel.set_window(window_active, &format_frequency(nav1_state.active_freq))?;
+    radio_panel.set_window(window_standby, &formatted_distance)?;
+    // warn about not receiving a DME signal
+    radio_panel.set_window_blinking(window_standby, dme_state.distance.is_none());
+    radio_panel.update_all_windows()
+}
+
+/// The whole panel shows a single page, both halves work together
+fn handle_page(
+    page: Page,
+    input: InputState,
+    state: &mut InstrumentStates,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+    simulator: &mut dyn SimulatorBackend,
+) -> Result<(), RadioPanelError> {
+    match page {
+        Page::Autopilot => {
+            let before = autopilot_events(&state.autopilot_state);
+            let toggled = apply_autopilot_input(&mut state.autopilot_state, input);
+            autopilot_logic(
+                &state.autopilot_state,
+                &before,
+                simulator,
+                radio_panel,
+                toggled,
+            )
+        }
+        Page::Courses => {
+            let before = course_events(&state.nav1_course, &state.nav2_course);
+            apply_courses_input(&mut state.nav1_course, &mut state.nav2_course, input);
+            courses_logic(state, &before, simulator, radio_panel)
+        }
+        Page::Barometer => {
+            let before = barometer_events(&state.barometer_state);
+            apply_barometer_input(&mut state.barometer_state, input);
+            barometer_logic(&state.barometer_state, &before, simulator, radio_panel)
+        }
+        Page::MachAndFlightPath => {
+            let before = mach_and_flight_path_events(&state.autopilot_state);
+            apply_mach_and_flight_path_input(&mut state.autopilot_state, input);
+            mach_and_flight_path_logic(&state.autopilot_state, &before, simulator, radio_panel)
+        }
+    }
+}
+
+/// NAV1 on top and NAV2 below, each active frequency with its course
+fn courses_logic(
+    state: &InstrumentStates,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+) -> Result<(), RadioPanelError> {
+    let after = course_events(&state.nav1_course, &state.nav2_course);
+    send_changes_to_sim(simulator, before, &after);
+    for (frequency_state, course, left_window, right_window) in [
+        (
+            &state.nav1_state,
+            state.nav1_course.degrees,
+            Window::TopLeft,
+            Window::TopRight,
+        ),
+        (
+            &state.nav2_state,
+            state.nav2_course.degrees,
+            Window::BottomLeft,
+            Window::BottomRight,
+        ),
+    ] {
+        radio_panel.set_window(left_window, &format_frequency(frequency_state.active_freq))?;
+        radio_panel.set_window(right_window, &format!("  {:0>3}", course))?;
+    }
+    radio_panel.update_all_windows()
+}
+
+/// The same setting twice, in hPa on top and in inHg below
+fn barometer_logic(
+    state: &BarometerState,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+) -> Result<(), RadioPanelError> {
+    send_changes_to_sim(simulator, before, &barometer_events(state));
+
+    let hectopascal = (f64::from(state.pascal) / 100.0).round() as i32;
+    let inhg_hundredths = (f64::from(state.pascal) / PASCAL_PER_INHG * 100.0).round() as i32;
+    radio_panel.set_window(Window::TopLeft, "     ")?;
+    radio_panel.set_window(Window::TopRight, &format!("{:>5}", hectopascal))?;
+    radio_panel.set_window(Window::BottomLeft, "     ")?;
+    radio_panel.set_window(
+        Window::BottomRight,
+        &format!("{:>3}.{:0>2}", inhg_hundredths / 100, inhg_hundredths % 100),
+    )?;
+    radio_panel.update_all_windows()
+}
+
+/// Mach number on top, flight path angle below
+fn mach_and_flight_path_logic(
+    state: &AutopilotState,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+) -> Result<(), RadioPanelError> {
+    send_changes_to_sim(simulator, before, &mach_and_flight_path_events(state));
+
+    let supports = |target| simulator.supports(SimVariable::AutopilotValue(target));
+    let mach = if supports(AutopilotTarget::Mach) {
+        format!("{:>3}.{:0>2}", state.mach / 100, state.mach % 100)
+    } else {
+        "     ".to_string()
+    };
+    let angle = state.flight_path_angle;
+    let flight_path_angle = if supports(AutopilotTarget::FlightPathAngle) {
+        let sign = if angle < 0 { "-" } else { "" };
+        format!(
+            "{:>4}.{}",
+            format!("{}{}", sign, angle.abs() / 10),
+            angle.abs() % 10
+        )
+    } else {
+        "     ".to_string()
     };
+    radio_panel.set_window(Window::TopLeft, "     ")?;
+    radio_panel.set_window(Window::TopRight, &mach)?;
+    radio_panel.set_window(Window::BottomLeft, "     ")?;
+    radio_panel.set_window(Window::BottomRight, &flight_path_angle)?;
+    radio_panel.update_all_windows()
+}
 
-    let selected_indicator_vertical_speed = match state.selected_setting {
-        AutopilotValue::Altitude => "",
-        AutopilotValue::VerticalSpeed => ".",
+fn autopilot_logic(
+    state: &AutopilotState,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+    toggled: Option<AutopilotMode>,
+) -> Result<(), RadioPanelError> {
+    if let Some(mode) = toggled {
+        simulator.send(SimEvent::ToggleAutopilot(mode));
+    }
+    send_changes_to_sim(simulator, before, &autopilot_events(state));
+    // a point behind a value shows its hold is engaged, one in front that the autopilot is on
+    let engaged = |mode, text: String| match state.engaged.contains(&mode) {
+        true => text + ".",
+        false => text,
     };
+    let mut airspeed = engaged(AutopilotMode::Airspeed, format!("{:>5}", state.airspeed));
+    if state.engaged.contains(&AutopilotMode::Master) {
+        airspeed.insert(1, '.');
+    }
+    radio_panel.set_window(Window::TopLeft, &airspeed)?;
+    radio_panel.set_window(
+        Window::TopRight,
+        &engaged(AutopilotMode::Heading, format!("  {:0>3}", state.heading)),
+    )?;
 
     radio_panel.set_window(
         Window::BottomLeft,
-        &format!("{:0>5}{}", state.altitude, selected_indicator_altitude),
-    );
+        &engaged(AutopilotMode::Altitude, format!("{:0>5}", state.altitude)),
+    )?;
 
     // make sure formatting is the same as in an Airbus (align right, always display 4 digits, sign in front)
-    match state.vertical_speed {
-        s if s >= 0 => radio_panel.set_window(
-            Window::BottomRight,
-            &format!(
-                " {:0>4}{}",
-                state.vertical_speed, selected_indicator_vertical_speed
-            ),
-        ),
-        s if s < 0 => radio_panel.set_window(
-            Window::BottomRight,
-            &format!(
-                "{:05}{}",
-                state.vertical_speed, selected_indicator_vertical_speed
-            ),
-        ),
+    let vertical_speed = match state.vertical_speed {
+        s if s >= 0 => format!(" {:0>4}", s),
+        s => format!("{:05}", s),
+    };
+    radio_panel.set_window(
+        Window::BottomRight,
+        &engaged(AutopilotMode::VerticalSpeed, vertical_speed),
+    )?;
+
+    // the value the lower knobs currently change
+    let selected_window = match state.selected_setting {
+        AutopilotValue::Altitude => Window::BottomLeft,
+        AutopilotValue::VerticalSpeed => Window::BottomRight,
+    };
+    radio_panel.set_window_blinking(selected_window, true);
+    radio_panel.update_all_windows()
+}
+
+/// Outer knob tunes whole MHz, inner knob steps through the channels
+/// A double press switches the knobs between the standby frequency and the course,
+/// so a press only swaps once it can't be the first of one anymore
+/// Returns whether the frequencies were swapped
+fn apply_nav_input(
+    frequency_state: &mut FrequencyState,
+    course: &mut CourseState,
+    swap_button: ButtonState,
+    outer_rotary: RotaryState,
+    inner_rotary: RotaryState,
+    rotary_steps: i16,
+    now: Instant,
+) -> bool {
+    match swap_button {
+        ButtonState::Pressed => course.swap_at = Some(now + course.swap_delay),
+        ButtonState::DoublePressed => {
+            course.swap_at = None;
+            course.obs_mode = !course.obs_mode;
+        }
         _ => (),
     }
-    radio_panel.update_all_windows();
+    let swapped = course.swap_at.is_some_and(|swap_at| now >= swap_at);
+    if swapped {
+        course.swap_at = None;
+        swap_frequencies(frequency_state);
+    }
+
+    if course.obs_mode {
+        apply_course_input(
+            &mut course.degrees,
+            outer_rotary,
+            inner_rotary,
+            rotary_steps,
+        );
+    } else {
+        // the button was taken care of
+        apply_frequency_input(
+            frequency_state,
+            ButtonState::Released,
+            outer_rotary,
+            inner_rotary,
+            rotary_steps,
+        );
+    }
+    swapped
 }
 
-fn apply_com_input(
+fn apply_frequency_input(
     frequency_state: &mut FrequencyState,
     swap_button: ButtonState,
     outer_rotary: RotaryState,
     inner_rotary: RotaryState,
+    rotary_steps: i16,
 ) {
     if matches!(swap_button, ButtonState::Pressed) {
         swap_frequencies(frequency_state);
     }
 
-    frequency_state.standby_freq.integer += match outer_rotary {
-        RotaryState::Clockwise => 1,
-        RotaryState::CounterClockwise => -1,
-        RotaryState::None => 0,
-    };
-    frequency_state.standby_freq.fraction += match inner_rotary {
-        RotaryState::Clockwise => 5,
-        RotaryState::CounterClockwise => -5,
-        RotaryState::None => 0,
-    };
+    let mhz_steps = rotary_steps
+        * match outer_rotary {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
+    frequency_state.standby_freq = frequency_state
+        .standby_freq
+        .add_hz(i32::from(mhz_steps) * 1_000_000);
 
-    frequency_state.standby_freq.integer = wrap(frequency_state.standby_freq.integer, 118, 137);
-    frequency_state.standby_freq.fraction = wrap(frequency_state.standby_freq.fraction, 0, 1000);
+    let channel_steps = rotary_steps
+        * match inner_rotary {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
+    if channel_steps != 0 {
+        let standby = frequency_state.standby_freq;
+        frequency_state.standby_freq = standby.with_fraction(
+            frequency_state
+                .channel_spacing
+                .step(standby.fraction(), channel_steps),
+        );
+    }
 }
 
-fn apply_nav_input(
-    frequency_state: &mut FrequencyState,
+/// Outer knob turns the mode switch, inner knob the selected digit of the code being dialed
+/// The button moves on to the next digit, past the last one the code is taken over right away
+fn apply_xpdr_input(
+    state: &mut XpdrState,
     swap_button: ButtonState,
     outer_rotary: RotaryState,
     inner_rotary: RotaryState,
+    now: Instant,
 ) {
-    if matches!(swap_button, ButtonState::Pressed) {
-        swap_frequencies(frequency_state);
+    match swap_button {
+        ButtonState::Pressed => {
+            state.last_edit = now;
+            state.digit_before_press = state.selected_digit;
+            state.selected_digit += 1;
+            if state.selected_digit == 4 {
+                state.selected_digit = 0;
+                state.confirm_at = Some(now + state.confirm_delay);
+            }
+        }
+        ButtonState::DoublePressed => recall_quick_code(state),
+        // IDENT, the press it started with doesn't move on
+        ButtonState::Held => {
+            state.selected_digit = state.digit_before_press;
+            state.confirm_at = None;
+        }
+        _ => (),
     }
 
-    frequency_state.standby_freq.integer += match outer_rotary {
+    // Stops at OFF and ALT like the real switch, no way to switch off by turning too far
+    let position = TransponderMode::ALL
+        .iter()
+        .position(|mode| *mode == state.mode)
+        .unwrap() as i8;
+    let position = position
+        + match outer_rotary {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
+    state.mode = TransponderMode::ALL[position.clamp(0, 3) as usize];
+
+    let digit_change = match inner_rotary {
         RotaryState::Clockwise => 1,
         RotaryState::CounterClockwise => -1,
         RotaryState::None => 0,
     };
-    frequency_state.standby_freq.fraction += match inner_rotary {
-        RotaryState::Clockwise => 50,
-        RotaryState::CounterClockwise => -50,
-        RotaryState::None => 0,
-    };
+    if digit_change != 0 {
+        let mut code = state.pending_code.unwrap_or(state.code);
+        code[state.selected_digit] = wrap(code[state.selected_digit] + digit_change, 0, 8);
+        state.pending_code = Some(code);
+        state.last_edit = now;
+        state.confirm_at = None; // still dialing
+    }
 
-    frequency_state.standby_freq.integer = wrap(frequency_state.standby_freq.integer, 108, 118);
-    frequency_state.standby_freq.fraction = wrap(frequency_state.standby_freq.fraction, 0, 1000);
+    if state.confirm_at.is_some_and(|confirm_at| now >= confirm_at)
+        || now.saturating_duration_since(state.last_edit) >= state.commit_delay
+    {
+        commit_xpdr_code(state);
+    }
 }
 
-fn apply_xpdr_input(
-    state: &mut XpdrState,
+/// Squawk the code being dialed, if any
+fn commit_xpdr_code(state: &mut XpdrState) {
+    if let Some(code) = state.pending_code.take() {
+        state.code = code;
+    }
+    state.confirm_at = None;
+}
+
+/// Squawk the quick code after the current one, or the first one for any other code
+fn recall_quick_code(state: &mut XpdrState) {
+    let next = state
+        .quick_codes
+        .iter()
+        .position(|code| *code == state.code)
+        .map_or(0, |index| (index + 1) % state.quick_codes.len());
+    if let Some(code) = state.quick_codes.get(next) {
+        state.code = *code;
+    }
+    state.pending_code = None;
+    state.confirm_at = None;
+    state.selected_digit = 0;
+}
+
+fn apply_adf_input(
+    adf_state: &mut AdfState,
     swap_button: ButtonState,
     outer_rotary: RotaryState,
     inner_rotary: RotaryState,
+    rotary_steps: i16,
 ) {
     if matches!(swap_button, ButtonState::Pressed) {
-        state.selected_digit += 1;
-        state.selected_digit = wrap(state.selected_digit.try_into().unwrap(), 0, 4) as usize;
+        std::mem::swap(
+            &mut adf_state.active_frequency,
+            &mut adf_state.standby_frequency,
+        );
     }
-    state.code[state.selected_digit] += match outer_rotary {
-        RotaryState::Clockwise => 1,
-        RotaryState::CounterClockwise => -1,
-        RotaryState::None => 0,
-    };
-    state.code[state.selected_digit] += match inner_rotary {
-        RotaryState::Clockwise => 1,
-        RotaryState::CounterClockwise => -1,
-        RotaryState::None => 0,
-    };
-    state.code[state.selected_digit] = wrap(state.code[state.selected_digit], 0, 8);
+
+    let inner_step = if adf_state.half_khz_steps { 500 } else { 1000 };
+    let delta = i32::from(rotary_steps)
+        * (match outer_rotary {
+            RotaryState::Clockwise => 100_000,
+            RotaryState::CounterClockwise => -100_000,
+            RotaryState::None => 0,
+        } + match inner_rotary {
+            RotaryState::Clockwise => inner_step,
+            RotaryState::CounterClockwise => -inner_step,
+            RotaryState::None => 0,
+        });
+    adf_state.standby_frequency = adf_state.standby_frequency.add_hz(delta);
 }
 
+/// Knobs change the values, button gestures toggle the modes holding them
 fn apply_autopilot_input(
     autopilot_state: &mut AutopilotState,
-    outer_rotary_upper: RotaryState,
-    inner_rotary_upper: RotaryState,
-    outer_rotary_lower: RotaryState,
-    inner_rotary_lower: RotaryState,
-    select_button: ButtonState,
-) {
-    if matches!(select_button, ButtonState::Pressed) {
+    input: InputState,
+) -> Option<AutopilotMode> {
+    let toggled = match (input.button_upper, input.button_lower) {
+        (ButtonState::DoublePressed, _) => Some(AutopilotMode::Heading),
+        (ButtonState::Held, _) => Some(AutopilotMode::Airspeed),
+        // both presses switched the selection, so it is back on the value double pressed
+        (_, ButtonState::DoublePressed) => Some(match autopilot_state.selected_setting {
+            AutopilotValue::Altitude => AutopilotMode::Altitude,
+            AutopilotValue::VerticalSpeed => AutopilotMode::VerticalSpeed,
+        }),
+        (_, ButtonState::Held) => Some(AutopilotMode::Master),
+        _ => None,
+    };
+    // switching back on a long press undoes what its press switched
+    if matches!(input.button_lower, ButtonState::Pressed | ButtonState::Held) {
         autopilot_state.selected_setting = match autopilot_state.selected_setting {
             AutopilotValue::Altitude => AutopilotValue::VerticalSpeed,
             AutopilotValue::VerticalSpeed => AutopilotValue::Altitude,
         }
     }
+    if let Some(mode) = toggled {
+        // shown right away, the next sync corrects it if the simulator refuses
+        if !autopilot_state.engaged.remove(&mode) {
+            autopilot_state.engaged.insert(mode);
+        }
+    }
 
-    autopilot_state.heading += match outer_rotary_upper {
-        RotaryState::Clockwise => 1,
-        RotaryState::CounterClockwise => -1,
-        RotaryState::None => 0,
-    };
+    autopilot_state.heading += input.rotary_steps
+        * match input.rotary_upper_outer {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
     autopilot_state.heading = wrap(autopilot_state.heading, 0, 360);
 
-    autopilot_state.airspeed += match inner_rotary_upper {
-        RotaryState::Clockwise => 1,
-        RotaryState::CounterClockwise => -1,
-        RotaryState::None => 0,
-    };
+    autopilot_state.airspeed += input.rotary_steps
+        * match input.rotary_upper_inner {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
     autopilot_state.airspeed = autopilot_state.airspeed.clamp(0, 9999);
 
     match autopilot_state.selected_setting {
         AutopilotValue::Altitude => {
-            autopilot_state.altitude += match outer_rotary_lower {
-                RotaryState::Clockwise => 1000,
-                RotaryState::CounterClockwise => -1000,
-                RotaryState::None => 0,
-            };
+            autopilot_state.altitude += i32::from(input.rotary_steps)
+                * match input.rotary_lower_outer {
+                    RotaryState::Clockwise => 1000,
+                    RotaryState::CounterClockwise => -1000,
+                    RotaryState::None => 0,
+                };
 
-            autopilot_state.altitude += match inner_rotary_lower {
-                RotaryState::Clockwise => 100,
-                RotaryState::CounterClockwise => -100,
-                RotaryState::None => 0,
-            };
-            autopilot_state.altitude = autopilot_state.altitude.clamp(100, 100000);
+            autopilot_state.altitude += i32::from(input.rotary_steps)
+                * match input.rotary_lower_inner {
+                    RotaryState::Clockwise => 100,
+                    RotaryState::CounterClockwise => -100,
+                    RotaryState::None => 0,
+                };
+            let (lowest, highest) = AUTOPILOT_ALTITUDE_RANGE;
+            autopilot_state.altitude = autopilot_state.altitude.clamp(lowest, highest);
         }
         AutopilotValue::VerticalSpeed => {
             // altitude
-            autopilot_state.vertical_speed += match outer_rotary_lower {
-                RotaryState::Clockwise => 100,
-                RotaryState::CounterClockwise => -100,
+            autopilot_state.vertical_speed += input.rotary_steps
+                * match input.rotary_lower_outer {
+                    RotaryState::Clockwise => 100,
+                    RotaryState::CounterClockwise => -100,
+                    RotaryState::None => 0,
+                };
+
+            autopilot_state.vertical_speed += input.rotary_steps
+                * match input.rotary_lower_inner {
+                    RotaryState::Clockwise => 100,
+                    RotaryState::CounterClockwise => -100,
+                    RotaryState::None => 0,
+                };
+            autopilot_state.vertical_speed = autopilot_state.vertical_speed.clamp(-9900, 9900);
+        }
+    }
+    toggled
+}
+
+/// Upper knobs set the course of NAV1, lower knobs the one of NAV2
+fn apply_courses_input(
+    nav1_course: &mut CourseState,
+    nav2_course: &mut CourseState,
+    input: InputState,
+) {
+    apply_course_input(
+        &mut nav1_course.degrees,
+        input.rotary_upper_outer,
+        input.rotary_upper_inner,
+        input.rotary_steps,
+    );
+    apply_course_input(
+        &mut nav2_course.degrees,
+        input.rotary_lower_outer,
+        input.rotary_lower_inner,
+        input.rotary_steps,
+    );
+}
+
+/// Outer knob in 10 degree steps, inner knob in single degrees
+fn apply_course_input(
+    course: &mut i16,
+    outer_rotary: RotaryState,
+    inner_rotary: RotaryState,
+    rotary_steps: i16,
+) {
+    *course += rotary_steps
+        * match outer_rotary {
+            RotaryState::Clockwise => 10,
+            RotaryState::CounterClockwise => -10,
+            RotaryState::None => 0,
+        };
+    *course += rotary_steps
+        * match inner_rotary {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
+    *course = wrap(*course, 0, 360);
+}
+
+/// Upper knobs step through whole hPa, lower knobs through hundredths of an inHg
+fn apply_barometer_input(barometer_state: &mut BarometerState, input: InputState) {
+    let pascal = f64::from(barometer_state.pascal);
+
+    let hectopascal_steps = input.rotary_steps
+        * match input.rotary_upper_outer {
+            RotaryState::Clockwise => 10,
+            RotaryState::CounterClockwise => -10,
+            RotaryState::None => 0,
+        }
+        + input.rotary_steps
+            * match input.rotary_upper_inner {
+                RotaryState::Clockwise => 1,
+                RotaryState::CounterClockwise => -1,
                 RotaryState::None => 0,
             };
+    if hectopascal_steps != 0 {
+        let hectopascal = (pascal / 100.0).round() + f64::from(hectopascal_steps);
+        barometer_state.pascal = (hectopascal * 100.0) as i32;
+    }
 
-            autopilot_state.vertical_speed += match inner_rotary_lower {
-                RotaryState::Clockwise => 100,
-                RotaryState::CounterClockwise => -100,
+    let inhg_steps = input.rotary_steps
+        * match input.rotary_lower_outer {
+            RotaryState::Clockwise => 10,
+            RotaryState::CounterClockwise => -10,
+            RotaryState::None => 0,
+        }
+        + input.rotary_steps
+            * match input.rotary_lower_inner {
+                RotaryState::Clockwise => 1,
+                RotaryState::CounterClockwise => -1,
                 RotaryState::None => 0,
             };
-            autopilot_state.vertical_speed = autopilot_state.vertical_speed.clamp(-9900, 9900);
-        }
+    if inhg_steps != 0 {
+        let inhg_hundredths = (pascal / PASCAL_PER_INHG * 100.0).round() + f64::from(inhg_steps);
+        barometer_state.pascal = (inhg_hundredths * PASCAL_PER_INHG / 100.0).round() as i32;
+    }
+
+    if hectopascal_steps != 0 || inhg_steps != 0 {
+        // about what altimeters can be set to, 950 to 1050 hPa
+        barometer_state.pascal = barometer_state.pascal.clamp(95_000, 105_000);
     }
 }
 
-fn send_com_to_sim(
-    com_state: &mut FrequencyState,
-    simulator: &SimConnector,
-    active_event_id: u32,
-    standby_event_id: u32,
+/// Upper knobs set the Mach number, lower knobs the flight path angle, inner in the last digit
+fn apply_mach_and_flight_path_input(autopilot_state: &mut AutopilotState, input: InputState) {
+    autopilot_state.mach += input.rotary_steps
+        * match input.rotary_upper_outer {
+            RotaryState::Clockwise => 10,
+            RotaryState::CounterClockwise => -10,
+            RotaryState::None => 0,
+        };
+    autopilot_state.mach += input.rotary_steps
+        * match input.rotary_upper_inner {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
+    autopilot_state.mach = autopilot_state.mach.clamp(0, 999);
+
+    autopilot_state.flight_path_angle += input.rotary_steps
+        * match input.rotary_lower_outer {
+            RotaryState::Clockwise => 10,
+            RotaryState::CounterClockwise => -10,
+            RotaryState::None => 0,
+        };
+    autopilot_state.flight_path_angle += input.rotary_steps
+        * match input.rotary_lower_inner {
+            RotaryState::Clockwise => 1,
+            RotaryState::CounterClockwise => -1,
+            RotaryState::None => 0,
+        };
+    autopilot_state.flight_path_angle = autopilot_state.flight_path_angle.clamp(-99, 99);
+}
+
+/// A radio's frequencies, as the events setting them in the simulator
+fn frequency_events(frequency_state: &FrequencyState, radio: Radio) -> Vec<SimEvent> {
+    let spacing = frequency_state.channel_spacing;
+    radio_events(
+        radio,
+        spacing.hz(frequency_state.active_freq),
+        spacing.hz(frequency_state.standby_freq),
+    )
+}
+
+fn nav_events(
+    frequency_state: &FrequencyState,
+    course: &CourseState,
+    radio: Radio,
+) -> Vec<SimEvent> {
+    let mut events = frequency_events(frequency_state, radio);
+    events.push(SimEvent::Course {
+        radio,
+        degrees: course.degrees as u16,
+    });
+    events
+}
+
+fn adf_events(adf_state: &AdfState) -> Vec<SimEvent> {
+    radio_events(
+        Radio::Adf,
+        adf_state.active_frequency.hz(),
+        adf_state.standby_frequency.hz(),
+    )
+}
+
+fn radio_events(radio: Radio, active_hz: u32, standby_hz: u32) -> Vec<SimEvent> {
+    vec![
+        SimEvent::ActiveFrequency {
+            radio,
+            frequency_hz: active_hz,
+        },
+        SimEvent::StandbyFrequency {
+            radio,
+            frequency_hz: standby_hz,
+        },
+    ]
+}
+
+fn xpdr_events(xpdr_state: &XpdrState) -> Vec<SimEvent> {
+    // e.g. [7, 0, 0, 0] is 7000
+    let code = xpdr_state
+        .code
+        .iter()
+        .fold(0, |code, digit| code * 10 + *digit as u16);
+    vec![
+        SimEvent::TransponderCode(code),
+        SimEvent::TransponderMode(xpdr_state.mode),
+    ]
+}
+
+fn autopilot_events(state: &AutopilotState) -> Vec<SimEvent> {
+    [
+        (AutopilotTarget::Heading, i32::from(state.heading)),
+        (AutopilotTarget::Altitude, state.altitude),
+        (
+            AutopilotTarget::VerticalSpeed,
+            i32::from(state.vertical_speed),
+        ),
+        (AutopilotTarget::Airspeed, i32::from(state.airspeed)),
+    ]
+    .map(|(target, value)| SimEvent::AutopilotValue { target, value })
+    .to_vec()
+}
+
+fn mach_and_flight_path_events(state: &AutopilotState) -> Vec<SimEvent> {
+    [
+        (AutopilotTarget::Mach, state.mach),
+        (AutopilotTarget::FlightPathAngle, state.flight_path_angle),
+    ]
+    .map(|(target, value)| SimEvent::AutopilotValue {
+        target,
+        value: i32::from(value),
+    })
+    .to_vec()
+}
+
+fn course_events(nav1_course: &CourseState, nav2_course: &CourseState) -> Vec<SimEvent> {
+    [
+        (Radio::Nav1, nav1_course.degrees),
+        (Radio::Nav2, nav2_course.degrees),
+    ]
+    .map(|(radio, course)| SimEvent::Course {
+        radio,
+        degrees: course as u16,
+    })
+    .to_vec()
+}
+
+fn barometer_events(barometer_state: &BarometerState) -> Vec<SimEvent> {
+    vec![SimEvent::Barometer(barometer_state.pascal as u32)]
+}
+
+/// Only sends the values the input changed, from the events of the instrument before and after it
+/// The others are what the simulator had at the last sync or still the panel's defaults,
+/// sending them could undo a change made in the virtual cockpit since
+fn send_changes_to_sim(
+    simulator: &mut dyn SimulatorBackend,
+    before: &[SimEvent],
+    after: &[SimEvent],
 ) -> bool {
-    // Format for FS2020
-    let active_frequency = parse::<u32>(&format!(
-        "{:0>3}{:0>3}000",
-        com_state.active_freq.integer, com_state.active_freq.fraction
-    ))
-    .unwrap();
-    let standby_frequency = parse::<u32>(&format!(
-        "{:0>3}{:0>3}000",
-        com_state.standby_freq.integer, com_state.standby_freq.fraction
-    ))
-    .unwrap();
-
-    if !simulator.transmit_client_event(1, active_event_id, active_frequency, 5, 0) {
-        return false;
-    };
-    if !simulator.transmit_client_event(1, standby_event_id, standby_frequency, 5, 0) {
-        return false;
+    let mut changes = after
+        .iter()
+        .filter(|event| !before.contains(event))
+        .peekable();
+    if changes.peek().is_none() {
+        // nothing to send, still notice a lost simulator
+        return simulator.is_connected();
     }
-
-    true
+    changes.all(|event| simulator.send(*event))
 }
 
-fn send_xpdr_to_sim(
-    xpdr_state: &mut XpdrState,
-    simulator: &SimConnector,
-    xpdr_event_id: u32,
+fn send_frequency_to_sim(
+    frequency_state: &FrequencyState,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    radio: Radio,
+    swapped: bool,
 ) -> bool {
-    let code = xpdr_state.code.map(|d| d.to_string()).join("");
-    let hex = format!("0x{}", code);
-    let hex = parse::<u32>(&hex).unwrap();
-    simulator.transmit_client_event(1, xpdr_event_id, hex, 5, 0)
+    let after = frequency_events(frequency_state, radio);
+    send_radio_to_sim(simulator, radio, before, &after, swapped)
 }
 
 fn send_nav_to_sim(
-    nav_state: &mut FrequencyState,
-    simulator: &SimConnector,
-    active_event_id: u32,
-    standby_event_id: u32,
+    frequency_state: &FrequencyState,
+    course: &CourseState,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    radio: Radio,
+    swapped: bool,
 ) -> bool {
-    send_com_to_sim(nav_state, simulator, active_event_id, standby_event_id)
+    let after = nav_events(frequency_state, course, radio);
+    send_radio_to_sim(simulator, radio, before, &after, swapped)
+}
+
+fn send_adf_to_sim(
+    adf_state: &AdfState,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    swapped: bool,
+) -> bool {
+    send_radio_to_sim(
+        simulator,
+        Radio::Adf,
+        before,
+        &adf_events(adf_state),
+        swapped,
+    )
+}
+
+/// A swap is left to the simulator's own swap event, aircraft with custom avionics
+/// don't always take both frequencies being set. What the simulator ends up with is
+/// taken over with the next sync.
+fn send_radio_to_sim(
+    simulator: &mut dyn SimulatorBackend,
+    radio: Radio,
+    before: &[SimEvent],
+    after: &[SimEvent],
+    swapped: bool,
+) -> bool {
+    if !swapped {
+        return send_changes_to_sim(simulator, before, after);
+    }
+    if !simulator.send(SimEvent::SwapFrequencies(radio)) {
+        return false;
+    }
+    // what the swap changed is the simulator's business
+    let swapped_before: Vec<SimEvent> = before
+        .iter()
+        .map(|event| match *event {
+            SimEvent::ActiveFrequency {
+                radio,
+                frequency_hz,
+            } => SimEvent::StandbyFrequency {
+                radio,
+                frequency_hz,
+            },
+            SimEvent::StandbyFrequency {
+                radio,
+                frequency_hz,
+            } => SimEvent::ActiveFrequency {
+                radio,
+                frequency_hz,
+            },
+            event => event,
+        })
+        .collect();
+    send_changes_to_sim(simulator, &swapped_before, after)
+}
+
+/// Identing is a single press, it stops by itself after a few seconds
+fn send_xpdr_to_sim(
+    xpdr_state: &XpdrState,
+    before: &[SimEvent],
+    simulator: &mut dyn SimulatorBackend,
+    ident: bool,
+) -> bool {
+    if ident && !simulator.send(SimEvent::TransponderIdent) {
+        return false;
+    }
+    send_changes_to_sim(simulator, before, &xpdr_events(xpdr_state))
 }
 
 fn display_adf_on_hardware(
     adf_state: &AdfState,
     window_active: Window,
     window_standby: Window,
-    radio_panel: &mut RadioPanel,
-) {
-    radio_panel.set_window(window_active, "     ");
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+) -> Result<(), RadioPanelError> {
+    radio_panel.set_window(
+        window_active,
+        &format_adf_frequency(adf_state.active_frequency, adf_state.half_khz_steps),
+    )?;
     radio_panel.set_window(
         window_standby,
-        &format!("{: >5}", adf_state.standby_frequency),
-    );
-    radio_panel.update_all_windows();
+        &format_adf_frequency(adf_state.standby_frequency, adf_state.half_khz_steps),
+    )?;
+    radio_panel.update_all_windows()
+}
+
+/// kHz aligned right, e.g. "  345", with 0.5 kHz steps always with a decimal, e.g. " 345.0"
+fn format_adf_frequency(frequency: Frequency, half_khz_steps: bool) -> String {
+    if half_khz_steps {
+        format!("{:>6.1}", f64::from(frequency.hz()) / 1000.0)
+    } else {
+        format_frequency(frequency)
+    }
 }
 
 fn swap_frequencies(frequency_state: &mut FrequencyState) {
@@ -688,7 +1791,9 @@ fn swap_frequencies(frequency_state: &mut FrequencyState) {
 }
 
 /// Show only dashes to indicate no data recieved from sim yet
-fn show_standby_screen(radio_panel: &mut RadioPanel) {
+fn show_standby_screen(
+    radio_panel: &mut RadioPanel<impl PanelTransport>,
+) -> Result<(), RadioPanelError> {
     for window_index in 0..4 {
         let window = match window_index {
             0 => Window::TopLeft,
@@ -697,52 +1802,8 @@ fn show_standby_screen(radio_panel: &mut RadioPanel) {
             3 => Window::BottomRight,
             _ => Window::TopLeft,
         };
-        radio_panel.set_window(window, "-----");
-    }
-    radio_panel.update_all_windows();
-}
-
-struct DataStruct {
-    dist: f64,
-}
-
-fn read_dme_from_sim(simulator: &SimConnector) -> Option<f64> {
-    simulator.add_data_definition(
-        0,
-        "HSI DISTANCE",
-        "Nautical miles",
-        simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
-        u32::MAX,
-        0.0,
-    );
-    simulator.request_data_on_sim_object(
-        0,
-        0,
-        0,
-        simconnect::SIMCONNECT_PERIOD_SIMCONNECT_PERIOD_ONCE,
-        0,
-        0,
-        0,
-        0,
-    );
-
-    let mut returned_distance = 0.0;
-    if let Ok(DispatchResult::SimObjectData(data)) = simulator.get_next_message() {
-        unsafe {
-            if data.dwDefineID == 0 {
-                let sim_data_ptr = std::ptr::addr_of!(data.dwData) as *const DataStruct;
-                let sim_data_value = std::ptr::read_unaligned(sim_data_ptr);
-                let distance = sim_data_value.dist;
-
-                returned_distance = distance.abs();
-            }
-        }
-    }
-
-    // if wrong CDI mode is selected, MSFS returns invalid values
-    if returned_distance == 1.0 || returned_distance < 0.01 {
-        None
-    } else {
-        Some(returned_distance)
+        radio_panel.set_window(window, "-----")?;
     }
+    // Some other program might have used the panel while waiting for the simulator
+    radio_panel.force_update_all_windows()
 }
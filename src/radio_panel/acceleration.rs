@@ -0,0 +1,303 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::{events::*, hardware::ModeSelectorState, pages::Page};
+
+/// How many steps a single detent counts as, depending on how fast the knob is spun
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccelerationCurve {
+    /// Pairs of (maximum time since the previous detent, steps), checked in order
+    /// Slower detents count as a single step
+    pub thresholds: Vec<(Duration, i16)>,
+}
+
+impl AccelerationCurve {
+    /// Every detent is exactly one step
+    pub fn linear() -> AccelerationCurve {
+        AccelerationCurve {
+            thresholds: Vec::new(),
+        }
+    }
+
+    /// Only speeds up for really fast spins
+    pub fn gentle() -> AccelerationCurve {
+        AccelerationCurve {
+            thresholds: vec![(Duration::from_millis(40), 2)],
+        }
+    }
+
+    /// Speeds up less than the default, for knobs with big steps already
+    pub fn moderate() -> AccelerationCurve {
+        AccelerationCurve {
+            thresholds: vec![
+                (Duration::from_millis(40), 5),
+                (Duration::from_millis(80), 2),
+            ],
+        }
+    }
+
+    pub fn steps(&self, since_previous_detent: Duration) -> i16 {
+        self.thresholds
+            .iter()
+            .find(|(max_interval, _)| since_previous_detent <= *max_interval)
+            .map_or(1, |(_, steps)| *steps)
+    }
+}
+
+impl Default for AccelerationCurve {
+    fn default() -> AccelerationCurve {
+        AccelerationCurve {
+            thresholds: vec![
+                (Duration::from_millis(40), 10),
+                (Duration::from_millis(80), 5),
+                (Duration::from_millis(120), 2),
+            ],
+        }
+    }
+}
+
+/// Sits between the hardware events and the instruments,
+/// turning quick successions of detents into bigger steps
+pub struct RotaryAccelerator {
+    default_curve: AccelerationCurve,
+    curves: HashMap<(Knob, ModeSelectorState), AccelerationCurve>,
+    page_curves: HashMap<(Knob, Page), AccelerationCurve>,
+    previous_detents: HashMap<Knob, (Direction, Instant)>,
+}
+
+impl RotaryAccelerator {
+    pub fn new(default_curve: AccelerationCurve) -> RotaryAccelerator {
+        RotaryAccelerator {
+            default_curve,
+            curves: HashMap::new(),
+            page_curves: HashMap::new(),
+            previous_detents: HashMap::new(),
+        }
+    }
+
+    /// Use a different curve for a knob while its mode selector is in a given position
+    pub fn set_curve(&mut self, knob: Knob, mode: ModeSelectorState, curve: AccelerationCurve) {
+        self.curves.insert((knob, mode), curve);
+    }
+
+    /// Use a different curve for a knob while a page is shown, whichever position it is in
+    pub fn set_page_curve(&mut self, knob: Knob, page: Page, curve: AccelerationCurve) {
+        self.page_curves.insert((knob, page), curve);
+    }
+
+    /// Returns how many steps a detent at the given time counts as
+    /// Changing direction always starts over with a single step
+    /// A curve for the page shown goes before the one for the mode
+    pub fn steps(
+        &mut self,
+        knob: Knob,
+        direction: Direction,
+        mode: ModeSelectorState,
+        page: Option<Page>,
+        time: Instant,
+    ) -> i16 {
+        let previous_detent = self.previous_detents.insert(knob, (direction, time));
+
+        match previous_detent {
+            Some((previous_direction, previous_time)) if previous_direction == direction => page
+                .and_then(|page| self.page_curves.get(&(knob, page)))
+                .or_else(|| self.curves.get(&(knob, mode)))
+                .unwrap_or(&self.default_curve)
+                .steps(time.saturating_duration_since(previous_time)),
+            _ => 1,
+        }
+    }
+}
+
+impl Default for RotaryAccelerator {
+    /// Fast spins on every knob, except for the ones changing whole MHz
+    /// and the transponder digits which only go from 0 to 7
+    /// On the autopilot page the lower knobs change altitude and vertical speed by 100 ft already
+    fn default() -> RotaryAccelerator {
+        let mut accelerator = RotaryAccelerator::new(AccelerationCurve::default());
+
+        for (outer_knob, inner_knob) in [
+            (Knob::UpperOuter, Knob::UpperInner),
+            (Knob::LowerOuter, Knob::LowerInner),
+        ] {
+            for mode in [
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorCom2,
+                ModeSelectorState::ModeSelectorNav1,
+                ModeSelectorState::ModeSelectorNav2,
+            ] {
+                accelerator.set_curve(outer_knob, mode, AccelerationCurve::gentle());
+            }
+            accelerator.set_curve(
+                outer_knob,
+                ModeSelectorState::ModeSelectorXpdr,
+                AccelerationCurve::linear(),
+            );
+            accelerator.set_curve(
+                inner_knob,
+                ModeSelectorState::ModeSelectorXpdr,
+                AccelerationCurve::linear(),
+            );
+        }
+
+        for (knob, curve) in [
+            (Knob::UpperOuter, AccelerationCurve::default()),
+            (Knob::UpperInner, AccelerationCurve::default()),
+            (Knob::LowerOuter, AccelerationCurve::gentle()),
+            (Knob::LowerInner, AccelerationCurve::moderate()),
+        ] {
+            accelerator.set_page_curve(knob, Page::Autopilot, curve);
+        }
+
+        accelerator
+    }
+}
+
+#[cfg(test)]
+mod acceleration_tests {
+    use super::*;
+
+    const MODE: ModeSelectorState = ModeSelectorState::ModeSelectorCom1;
+
+    /// Feed detents at the given milliseconds and return the steps for each
+    fn spin(
+        accelerator: &mut RotaryAccelerator,
+        knob: Knob,
+        mode: ModeSelectorState,
+        detents: &[(u64, Direction)],
+    ) -> Vec<i16> {
+        let start = Instant::now();
+        detents
+            .iter()
+            .map(|(millis, direction)| {
+                accelerator.steps(
+                    knob,
+                    *direction,
+                    mode,
+                    None,
+                    start + Duration::from_millis(*millis),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_slow_turns_are_single_steps() {
+        let mut accelerator = RotaryAccelerator::new(AccelerationCurve::default());
+        let detents = [
+            (0, Direction::Clockwise),
+            (200, Direction::Clockwise),
+            (500, Direction::Clockwise),
+        ];
+        assert_eq!(
+            spin(&mut accelerator, Knob::UpperInner, MODE, &detents),
+            vec![1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn test_fast_turns_accelerate() {
+        let mut accelerator = RotaryAccelerator::new(AccelerationCurve::default());
+        let detents = [
+            (0, Direction::Clockwise),
+            (100, Direction::Clockwise),
+            (170, Direction::Clockwise),
+            (200, Direction::Clockwise),
+            (1000, Direction::Clockwise),
+        ];
+        assert_eq!(
+            spin(&mut accelerator, Knob::LowerInner, MODE, &detents),
+            vec![1, 2, 5, 10, 1]
+        );
+    }
+
+    #[test]
+    fn test_direction_change_resets() {
+        let mut accelerator = RotaryAccelerator::new(AccelerationCurve::default());
+        let detents = [
+            (0, Direction::Clockwise),
+            (20, Direction::Clockwise),
+            (40, Direction::CounterClockwise),
+            (60, Direction::CounterClockwise),
+        ];
+        assert_eq!(
+            spin(&mut accelerator, Knob::LowerInner, MODE, &detents),
+            vec![1, 10, 1, 10]
+        );
+    }
+
+    #[test]
+    fn test_knobs_are_independent() {
+        let mut accelerator = RotaryAccelerator::new(AccelerationCurve::default());
+        let start = Instant::now();
+        accelerator.steps(Knob::UpperInner, Direction::Clockwise, MODE, None, start);
+        let steps = accelerator.steps(
+            Knob::LowerInner,
+            Direction::Clockwise,
+            MODE,
+            None,
+            start + Duration::from_millis(10),
+        );
+        assert_eq!(steps, 1);
+    }
+
+    #[test]
+    fn test_curve_per_knob_and_mode() {
+        let mut accelerator = RotaryAccelerator::default();
+        let detents = [(0, Direction::Clockwise), (30, Direction::Clockwise)];
+
+        let xpdr = ModeSelectorState::ModeSelectorXpdr;
+        let adf = ModeSelectorState::ModeSelectorAdf;
+        assert_eq!(
+            spin(&mut accelerator, Knob::UpperInner, xpdr, &detents),
+            vec![1, 1]
+        );
+        assert_eq!(
+            spin(&mut accelerator, Knob::UpperOuter, MODE, &detents),
+            vec![1, 2]
+        );
+        assert_eq!(
+            spin(&mut accelerator, Knob::LowerOuter, adf, &detents),
+            vec![1, 10]
+        );
+    }
+
+    #[test]
+    fn test_curve_per_page() {
+        let spin_on_page = |knob, page| {
+            let mut accelerator = RotaryAccelerator::default();
+            let start = Instant::now();
+            [0, 30, 60]
+                .map(|millis| {
+                    accelerator.steps(
+                        knob,
+                        Direction::Clockwise,
+                        MODE,
+                        page,
+                        start + Duration::from_millis(millis),
+                    )
+                })
+                .to_vec()
+        };
+
+        // whole MHz on COM1
+        assert_eq!(spin_on_page(Knob::UpperOuter, None), vec![1, 2, 2]);
+        // the heading on the autopilot page
+        assert_eq!(
+            spin_on_page(Knob::UpperOuter, Some(Page::Autopilot)),
+            vec![1, 10, 10]
+        );
+        // 100 ft of altitude or vertical speed
+        assert_eq!(
+            spin_on_page(Knob::LowerInner, Some(Page::Autopilot)),
+            vec![1, 5, 5]
+        );
+        // pages without curves of their own go by the mode
+        assert_eq!(
+            spin_on_page(Knob::UpperOuter, Some(Page::Barometer)),
+            vec![1, 2, 2]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use super::constants::DEVICE_SEVEN_SEGMENT_COUNT;
+
+/// How long each frame of an animation is shown
+pub const ANIMATION_FRAME_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Moving content for a single window, repeats until cancelled
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Animation {
+    /// Text scrolling from right to left, can be longer than the window
+    Marquee(String),
+    /// A dash running from left to right
+    Sweep,
+    /// A dash bouncing back and forth
+    Spinner,
+}
+
+impl Animation {
+    /// The window contents, shown one after another
+    pub fn frames(&self) -> Vec<String> {
+        match self {
+            Animation::Marquee(text) => marquee_frames(text),
+            Animation::Sweep => (0..DEVICE_SEVEN_SEGMENT_COUNT).map(dash_at).collect(),
+            Animation::Spinner => (0..DEVICE_SEVEN_SEGMENT_COUNT)
+                .chain((1..DEVICE_SEVEN_SEGMENT_COUNT - 1).rev())
+                .map(dash_at)
+                .collect(),
+        }
+    }
+}
+
+/// A blank window with a single dash
+fn dash_at(position: usize) -> String {
+    let mut content = String::from("     ");
+    content.replace_range(position..position + 1, "-");
+    content
+}
+
+/// Scroll the text in from the right until it left the window on the left
+fn marquee_frames(text: &str) -> Vec<String> {
+    // A point belongs to the character before it, it doesn't take up a display of its own
+    let mut characters: Vec<String> = Vec::new();
+    for character in text.chars() {
+        match characters.last_mut() {
+            Some(previous) if character == '.' && !previous.ends_with('.') => previous.push('.'),
+            _ => characters.push(character.to_string()),
+        }
+    }
+
+    let blank = vec![String::from(" "); DEVICE_SEVEN_SEGMENT_COUNT];
+    let padded: Vec<String> = blank
+        .iter()
+        .chain(characters.iter())
+        .chain(blank.iter())
+        .cloned()
+        .collect();
+
+    padded
+        .windows(DEVICE_SEVEN_SEGMENT_COUNT)
+        .skip(1) // the fully blank frame is already shown at the end
+        .map(|frame| frame.concat())
+        .collect()
+}
+
+#[cfg(test)]
+mod animation_tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep() {
+        assert_eq!(
+            Animation::Sweep.frames(),
+            vec!["-    ", " -   ", "  -  ", "   - ", "    -"]
+        );
+    }
+
+    #[test]
+    fn test_spinner_bounces() {
+        let frames = Animation::Spinner.frames();
+        assert_eq!(frames.len(), 8);
+        assert_eq!(frames[4], "    -");
+        assert_eq!(frames[5], "   - ");
+        assert_eq!(frames[7], " -   ");
+    }
+
+    #[test]
+    fn test_marquee() {
+        let frames = Animation::Marquee(String::from("1.2345678")).frames();
+        assert_eq!(frames.len(), 13);
+        assert_eq!(frames[0], "    1.");
+        assert_eq!(frames[4], "1.2345");
+        assert_eq!(frames[7], "45678");
+        assert_eq!(frames[12], "     ");
+    }
+}
@@ -0,0 +1,117 @@
+use std::str::FromStr;
+
+use super::hardware::ModeSelectorState;
+
+/// Which instruments a Radio Panel is in charge of
+/// Lets several panels split the work, e.g. one for COM/NAV and one for autopilot and transponder
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanelAssignment {
+    pub modes: Vec<ModeSelectorState>,
+    pub autopilot: bool, // both mode selectors in the same position show a page, see PageRegistry
+}
+
+impl PanelAssignment {
+    /// Everything, what a single panel does
+    pub fn all() -> PanelAssignment {
+        PanelAssignment {
+            modes: vec![
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorCom2,
+                ModeSelectorState::ModeSelectorNav1,
+                ModeSelectorState::ModeSelectorNav2,
+                ModeSelectorState::ModeSelectorAdf,
+                ModeSelectorState::ModeSelectorDme,
+                ModeSelectorState::ModeSelectorXpdr,
+            ],
+            autopilot: true,
+        }
+    }
+
+    pub fn handles(&self, mode: ModeSelectorState) -> bool {
+        self.modes.contains(&mode)
+    }
+}
+
+impl FromStr for PanelAssignment {
+    type Err = String;
+
+    /// Parse a comma separated list like "com1,nav1,ap"
+    fn from_str(modes: &str) -> Result<PanelAssignment, String> {
+        let mut assignment = PanelAssignment {
+            modes: Vec::new(),
+            autopilot: false,
+        };
+
+        for mode in modes.split(',') {
+            if mode.trim().eq_ignore_ascii_case("ap") {
+                assignment.autopilot = true;
+                continue;
+            }
+            let mode = parse_mode(mode)?;
+            if !assignment.handles(mode) {
+                assignment.modes.push(mode);
+            }
+        }
+
+        Ok(assignment)
+    }
+}
+
+/// A mode selector position by the name printed next to it, e.g. "com1"
+pub fn parse_mode(mode: &str) -> Result<ModeSelectorState, String> {
+    match mode.trim().to_lowercase().as_str() {
+        "com1" => Ok(ModeSelectorState::ModeSelectorCom1),
+        "com2" => Ok(ModeSelectorState::ModeSelectorCom2),
+        "nav1" => Ok(ModeSelectorState::ModeSelectorNav1),
+        "nav2" => Ok(ModeSelectorState::ModeSelectorNav2),
+        "adf" => Ok(ModeSelectorState::ModeSelectorAdf),
+        "dme" => Ok(ModeSelectorState::ModeSelectorDme),
+        "xpdr" => Ok(ModeSelectorState::ModeSelectorXpdr),
+        unknown => Err(format!("Unknown mode: {:?}", unknown)),
+    }
+}
+
+/// Parse a command line argument of the form "<serial number or 1-based index>=<modes>"
+/// Returns which panel is meant and its assignment
+pub fn parse_panel_argument(argument: &str) -> Result<(String, PanelAssignment), String> {
+    match argument.split_once('=') {
+        Some((panel, modes)) if !panel.is_empty() => Ok((panel.to_string(), modes.parse()?)),
+        _ => Err(format!(
+            "Expected <serial number or index>=<modes>, got {:?}",
+            argument
+        )),
+    }
+}
+
+#[cfg(test)]
+mod assignment_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modes() {
+        let assignment: PanelAssignment = "com1, NAV1,ap".parse().unwrap();
+        assert_eq!(
+            assignment.modes,
+            vec![
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorNav1
+            ]
+        );
+        assert!(assignment.autopilot);
+        assert!(!assignment.handles(ModeSelectorState::ModeSelectorXpdr));
+    }
+
+    #[test]
+    fn test_unknown_mode() {
+        assert!("com1,tacan".parse::<PanelAssignment>().is_err());
+    }
+
+    #[test]
+    fn test_parse_panel_argument() {
+        let (panel, assignment) = parse_panel_argument("2=xpdr,ap").unwrap();
+        assert_eq!(panel, "2");
+        assert_eq!(assignment.modes, vec![ModeSelectorState::ModeSelectorXpdr]);
+        assert!(parse_panel_argument("xpdr").is_err());
+        assert!(parse_panel_argument("=xpdr").is_err());
+    }
+}
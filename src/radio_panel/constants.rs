@@ -4,6 +4,12 @@ pub const DEVICE_WINDOW_COUNT: usize = 4;
 /// Each window has 5 7-segment displays
 pub const DEVICE_SEVEN_SEGMENT_COUNT: usize = 5;
 
+/// Size of the report setting all displays, 2 bytes at end unused, required on Windows hidapi
+pub const CONTROL_MESSAGE_SIZE: usize = 23;
+
+/// Size of the report describing the state of all buttons, rotaries and mode selectors
+pub const INPUT_REPORT_SIZE: usize = 3;
+
 /// Show nothing instead of a digit
 pub const DIGIT_BLANK: u8 = 0b00001111;
 
@@ -53,18 +59,3 @@ pub const BITMASK_MODE_SELECTOR_LOWER_NAV2: u32 = 0b0000_0000_0000_0000_0000_010
 pub const BITMASK_MODE_SELECTOR_LOWER_ADF: u32 = 0b0000_0000_0000_0000_0000_1000_0000_0000;
 pub const BITMASK_MODE_SELECTOR_LOWER_DME: u32 = 0b0000_0000_0000_0000_0001_0000_0000_0000;
 pub const BITMASK_MODE_SELECTOR_LOWER_XPDR: u32 = 0b0000_0000_0000_0000_0010_0000_0000_0000;
-
-/* User defined event IDs for MSFS */
-pub const EVENT_ID_COM_RADIO_SET_HZ: u32 = 1000;
-pub const EVENT_ID_COM_STBY_RADIO_SET_HZ: u32 = 1001;
-pub const EVENT_ID_COM2_RADIO_SET_HZ: u32 = 1002;
-pub const EVENT_ID_COM2_STBY_RADIO_SET_HZ: u32 = 1003;
-pub const EVENT_ID_NAV1_RADIO_SET_HZ: u32 = 1004;
-pub const EVENT_ID_NAV1_STBY_SET_HZ: u32 = 1005;
-pub const EVENT_ID_NAV2_RADIO_SET_HZ: u32 = 1006;
-pub const EVENT_ID_NAV2_STBY_SET_HZ: u32 = 1007;
-pub const EVENT_ID_XPNDR_SET: u32 = 1008;
-pub const EVENT_ID_HEADING_BUG_SET: u32 = 1009;
-pub const EVENT_ID_AP_ALT_VAR_SET_ENGLISH: u32 = 1010;
-pub const EVENT_ID_AP_VS_VAR_SET_ENGLISH: u32 = 1011;
-pub const EVENT_ID_AP_SPD_VAR_SET: u32 = 1012;
@@ -1,15 +1,21 @@
-use core::panic;
-use hidapi::{HidApi, HidDevice};
-use std::process;
+use std::time::{Duration, Instant};
+
+use super::{
+    animation::{Animation, ANIMATION_FRAME_INTERVAL},
+    constants::*,
+    error::RadioPanelError,
+    events::*,
+    glyphs::*,
+    hardware::*,
+    transport::{HidPanels, HidTransport, PanelInfo, PanelTransport},
+};
 
-use super::{constants::*, hardware::*};
-
-const VENDOR_ID: u16 = 0x06a3; // Saitek
-const PRODUCT_ID: u16 = 0x0d05; // Radio Panel
+const NO_INPUTS_AFTER_TIMEOUT: u32 = 0;
 
-const CONTROL_MESSAGE_SIZE: usize = 23; // 2 bytes at end unused, required on Windows hidapi
+const DEFAULT_INPUT_TIMEOUT_MS: i32 = 300;
 
-const NO_INPUTS_AFTER_TIMEOUT: u32 = 0;
+/// How long blinking displays stay on and off
+const DEFAULT_BLINK_INTERVAL: Duration = Duration::from_millis(400);
 
 #[derive(Copy, Clone, Debug)]
 pub struct InputState {
@@ -21,6 +27,7 @@ pub struct InputState {
     pub rotary_lower_outer: RotaryState,
     pub button_upper: ButtonState,
     pub button_lower: ButtonState,
+    pub rotary_steps: i16, // how many steps the turned rotary counts as, more than 1 when spun fast
 }
 
 impl InputState {
@@ -34,42 +41,187 @@ impl InputState {
             rotary_lower_outer: RotaryState::None,
             button_upper: ButtonState::Released,
             button_lower: ButtonState::Released,
+            rotary_steps: 1,
+        }
+    }
+
+    /// Same mode selector positions, but no button or rotary action
+    pub fn idle(&self) -> InputState {
+        InputState {
+            mode_selector_upper: self.mode_selector_upper,
+            mode_selector_lower: self.mode_selector_lower,
+            ..InputState::new()
+        }
+    }
+
+    /// The input to act on for a single event, all other buttons and rotaries are idle
+    pub fn with_event(&self, event: InputEvent) -> InputState {
+        let mut input = self.idle();
+        match event {
+            InputEvent::ButtonPressed(Button::Upper) => input.button_upper = ButtonState::Pressed,
+            InputEvent::ButtonPressed(Button::Lower) => input.button_lower = ButtonState::Pressed,
+            InputEvent::ButtonHeld(Button::Upper) => input.button_upper = ButtonState::Held,
+            InputEvent::ButtonHeld(Button::Lower) => input.button_lower = ButtonState::Held,
+            InputEvent::ButtonDoublePressed(Button::Upper) => {
+                input.button_upper = ButtonState::DoublePressed
+            }
+            InputEvent::ButtonDoublePressed(Button::Lower) => {
+                input.button_lower = ButtonState::DoublePressed
+            }
+            InputEvent::ButtonReleased(_) => (),
+            InputEvent::RotaryTurned { knob, direction } => {
+                let rotary = match direction {
+                    Direction::Clockwise => RotaryState::Clockwise,
+                    Direction::CounterClockwise => RotaryState::CounterClockwise,
+                };
+                match knob {
+                    Knob::UpperInner => input.rotary_upper_inner = rotary,
+                    Knob::UpperOuter => input.rotary_upper_outer = rotary,
+                    Knob::LowerInner => input.rotary_lower_inner = rotary,
+                    Knob::LowerOuter => input.rotary_lower_outer = rotary,
+                }
+            }
+            InputEvent::ModeChanged { selector, to, .. } => match selector {
+                Selector::Upper => input.mode_selector_upper = to,
+                Selector::Lower => input.mode_selector_lower = to,
+            },
         }
+        input
     }
 }
 
+/// How many display updates actually went over USB
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DisplayStats {
+    pub reports_sent: u64,
+    pub reports_skipped: u64, // identical to what the hardware already shows
+}
+
 /// Represents the radio panel with its 4 windows, containing 5 7-segment displays each.
-pub struct RadioPanel {
-    hid_device: HidDevice,
+pub struct RadioPanel<T: PanelTransport = HidTransport> {
+    transport: T,
     windows: [RadioPanelWindow; 4],
+    last_sent_report: Option<[u8; CONTROL_MESSAGE_SIZE]>, // None if unknown what the hardware shows
+    display_stats: DisplayStats,
+    connected: bool, // false while waiting for the unplugged device to come back
+    last_input: InputState,
+    input_timeout_ms: i32,
+    blink_interval: Duration,
+    blink_started: Instant, // blinking displays are on during even intervals since then
+    animations: [Option<RunningAnimation>; DEVICE_WINDOW_COUNT],
+}
+
+/// An animation playing in a window
+struct RunningAnimation {
+    frames: Vec<[SevenSegmentDisplay; DEVICE_SEVEN_SEGMENT_COUNT]>,
+    started: Instant,
+}
+
+impl RunningAnimation {
+    fn frame_index(&self, now: Instant) -> usize {
+        let since_start = now.saturating_duration_since(self.started).as_millis();
+        (since_start / ANIMATION_FRAME_INTERVAL.as_millis()) as usize % self.frames.len()
+    }
 }
 
 impl RadioPanel {
-    pub fn new() -> RadioPanel {
+    /// Open a specific Radio Panel, found through HidPanels::list
+    pub fn open(hid_panels: &HidPanels, panel: &PanelInfo) -> Result<RadioPanel, RadioPanelError> {
+        Ok(RadioPanel::with_transport(hid_panels.open(panel)?))
+    }
+}
+
+impl<T: PanelTransport> RadioPanel<T> {
+    /// Drive the panel through any transport, e.g. a mock device
+    pub fn with_transport(transport: T) -> RadioPanel<T> {
         RadioPanel {
-            hid_device: HidApi::new()
-                .unwrap()
-                .open(VENDOR_ID, PRODUCT_ID)
-                .unwrap_or_else(|_error| {
-                    println!("Couldn't connect to hardware. Is it plugged in?");
-                    process::exit(1);
-                }),
+            transport,
             windows: [RadioPanelWindow {
                 displays: [SevenSegmentDisplay {
                     value: DIGIT_BLANK,
                     has_decimal_point: false,
+                    blinking: false,
                 }; DEVICE_SEVEN_SEGMENT_COUNT], // repeat for all 7-segement displays
+                blinking: false,
             }; DEVICE_WINDOW_COUNT], // repeat for all windows
+            last_sent_report: None,
+            display_stats: DisplayStats::default(),
+            connected: true,
+            last_input: InputState::new(),
+            input_timeout_ms: DEFAULT_INPUT_TIMEOUT_MS,
+            blink_interval: DEFAULT_BLINK_INTERVAL,
+            blink_started: Instant::now(),
+            animations: Default::default(),
         }
     }
 
+    /// How long blinking displays stay on, and then off
+    pub fn set_blink_interval(&mut self, interval: Duration) {
+        self.blink_interval = interval;
+    }
+
+    /// How long to wait for input at most
+    /// Lower it when polling several panels in turn
+    pub fn set_input_timeout(&mut self, timeout_ms: i32) {
+        self.input_timeout_ms = timeout_ms;
+    }
+
     /// Blocking call to wait for input from buttons or rotaries
+    /// Returns what changed on the hardware since the last call, nothing after a timeout
+    /// While the device is unplugged, this waits for it to come back and returns no events
+    pub fn block_until_events(&mut self) -> Result<Vec<InputEvent>, RadioPanelError> {
+        let input_state = match self.block_until_input()? {
+            Some(input_state) => input_state,
+            None => return Ok(Vec::new()),
+        };
+
+        let events = diff_input_states(&self.last_input, &input_state);
+        self.last_input = input_state;
+        Ok(events)
+    }
+
     /// Returns the current state of all buttons and potis on the hardware
-    pub fn block_until_input(&mut self) -> Option<InputState> {
-        let mut input_buffer = [0u8; 3];
-        self.hid_device
-            .read_timeout(&mut input_buffer, 300)
-            .expect("Error reading from device");
+    /// Wakes up early to let blinking displays blink and animations move, even without input
+    /// Any input stops all animations
+    fn block_until_input(&mut self) -> Result<Option<InputState>, RadioPanelError> {
+        if !self.connected && !self.try_reconnect()? {
+            return Ok(None);
+        }
+
+        let now = Instant::now();
+        let until_next_change = [self.until_next_blink(now), self.until_next_frame(now)]
+            .into_iter()
+            .flatten()
+            .min();
+        let timeout_ms = match until_next_change {
+            Some(until_next_change) => self
+                .input_timeout_ms
+                .min(until_next_change.as_millis() as i32 + 1),
+            None => self.input_timeout_ms,
+        };
+
+        let mut input_buffer = [0u8; INPUT_REPORT_SIZE];
+        let read_result = self
+            .transport
+            .read_input_report(&mut input_buffer, timeout_ms);
+
+        if matches!(read_result, Ok(bytes_read) if bytes_read > 0) {
+            self.cancel_all_animations();
+        }
+
+        if until_next_change.is_some() {
+            self.animate(Instant::now());
+            // Only actually sends something when a blinking display or animation changed
+            self.update_all_windows()?;
+        }
+
+        match read_result {
+            Err(RadioPanelError::Disconnected) => {
+                self.handle_disconnect();
+                return Ok(None);
+            }
+            result => result?,
+        };
 
         // Turn buffer array into a single 32 bit value
         let input_buffer = ((input_buffer[0] as u32) << 16)
@@ -78,7 +230,7 @@ impl RadioPanel {
         // println!("{:#034b}", buffer)
 
         if input_buffer == NO_INPUTS_AFTER_TIMEOUT {
-            return None;
+            return Ok(None);
         }
 
         let mut input_state = InputState::new();
@@ -120,7 +272,7 @@ impl RadioPanel {
                 dme: BITMASK_MODE_SELECTOR_UPPER_DME,
                 xpdr: BITMASK_MODE_SELECTOR_UPPER_XPDR,
             },
-        );
+        )?;
 
         input_state.mode_selector_lower = parse_mode_selector_state(
             input_buffer,
@@ -133,48 +285,156 @@ impl RadioPanel {
                 dme: BITMASK_MODE_SELECTOR_LOWER_DME,
                 xpdr: BITMASK_MODE_SELECTOR_LOWER_XPDR,
             },
-        );
+        )?;
 
-        Some(input_state)
+        Ok(Some(input_state))
     }
 
     /// Show values in window, previous values are cleared
-    /// value can contain up to 5 digits from 0-9 and an optional point.
-    /// Can also be a blank space or a dash
-    /// Examples: 12345, 123.45, 1.2.3.4.5., 12 45, 12-45
-    pub fn set_window(&mut self, window: Window, value: &str) {
-        self.set_window_additively(window, "     "); // clean window contents
-        self.set_window_additively(window, value);
-    }
-
-    /// Draws the values into a window without clearing it
-    /// If not all digits are set, previous digits can remain
-    fn set_window_additively(&mut self, window: Window, value: &str) {
-        let window_index = window as usize;
-        let mut display_index = 0;
-
-        for character in value.chars() {
-            // If a dot succeeds a digit, tell that previous digit it has a decimal point
-            if character == '.' {
-                self.windows[window_index].displays[display_index - 1].has_decimal_point = true;
-                continue;
-            }
-
-            self.windows[window_index].displays[display_index].has_decimal_point = false;
-            self.windows[window_index].displays[display_index].value = match character {
-                ' ' => DIGIT_BLANK,
-                '-' => DIGIT_DASH,
-                '0'..='9' => character.to_digit(10).unwrap() as u8,
-                _ => panic!("Impossible value for 7-segement to display"),
-            };
+    /// value can contain up to 5 characters from the glyph table, each with an optional point.
+    /// Digits, blank spaces, dashes and letters looking like digits
+    /// Examples: 12345, 123.45, 1.2.3.4.5., 12 45, 12-45, BOSS
+    /// Nothing is changed if the value can't be displayed
+    /// Stops any blinking in the window
+    pub fn set_window(&mut self, window: Window, value: &str) -> Result<(), RadioPanelError> {
+        let displays = encode_window(value)?;
+        self.animations[window as usize] = None;
+        self.windows[window as usize] = RadioPanelWindow {
+            displays,
+            blinking: false,
+        };
+        Ok(())
+    }
 
-            display_index += 1;
+    /// Play an animation in a window until it is cancelled, its value is set or any input arrives
+    /// Keeps running while waiting for input, nothing blocks
+    pub fn start_animation(
+        &mut self,
+        window: Window,
+        animation: &Animation,
+    ) -> Result<(), RadioPanelError> {
+        let frames = animation
+            .frames()
+            .iter()
+            .map(|frame| encode_window(frame))
+            .collect::<Result<Vec<_>, _>>()?;
+        if frames.is_empty() {
+            return Ok(());
         }
+
+        self.windows[window as usize] = RadioPanelWindow {
+            displays: frames[0],
+            blinking: false,
+        };
+        self.animations[window as usize] = Some(RunningAnimation {
+            frames,
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Stop all animations, their last frames stay visible
+    pub fn cancel_all_animations(&mut self) {
+        self.animations = Default::default();
+    }
+
+    #[cfg(test)]
+    pub fn is_animating(&self) -> bool {
+        self.animations.iter().any(Option::is_some)
+    }
+
+    /// Let the whole window blink, until its value is set again
+    /// e.g. for the value currently being edited
+    pub fn set_window_blinking(&mut self, window: Window, blinking: bool) {
+        self.windows[window as usize].blinking = blinking;
+    }
+
+    /// Let a single 7-segment display of a window blink, until the window's value is set again
+    /// display_index counts from the left, starting at 0
+    pub fn set_display_blinking(&mut self, window: Window, display_index: usize, blinking: bool) {
+        self.windows[window as usize].displays[display_index].blinking = blinking;
     }
 
     /// Show the data on all displays
-    pub fn update_all_windows(&self) {
+    /// Nothing is sent if the hardware already shows exactly this
+    /// While the device is unplugged, the data is kept and sent once it is back
+    pub fn update_all_windows(&mut self) -> Result<(), RadioPanelError> {
+        if !self.connected {
+            return Ok(());
+        }
+
+        let output_buffer = self.encode_report(Instant::now());
+        if self.last_sent_report == Some(output_buffer) {
+            self.display_stats.reports_skipped += 1;
+            return Ok(());
+        }
+        self.send_report(output_buffer)
+    }
+
+    /// Show the data on all displays, even if it didn't change
+    /// For when something else might have written to the hardware
+    pub fn force_update_all_windows(&mut self) -> Result<(), RadioPanelError> {
+        if !self.connected {
+            return Ok(());
+        }
+
+        let output_buffer = self.encode_report(Instant::now());
+        self.send_report(output_buffer)
+    }
+
+    pub fn display_stats(&self) -> DisplayStats {
+        self.display_stats
+    }
+
+    /// Are blinking displays on at the given time?
+    fn blink_phase_on(&self, now: Instant) -> bool {
+        let since_start = now.saturating_duration_since(self.blink_started);
+        (since_start.as_millis() / self.blink_interval.as_millis().max(1)).is_multiple_of(2)
+    }
+
+    /// How long until blinking displays turn on or off, None if nothing blinks
+    fn until_next_blink(&self, now: Instant) -> Option<Duration> {
+        let anything_blinking = self.windows.iter().any(|window| {
+            window.blinking || window.displays.iter().any(|display| display.blinking)
+        });
+        if !anything_blinking {
+            return None;
+        }
+
+        let interval = self.blink_interval.as_millis().max(1);
+        let since_start = now
+            .saturating_duration_since(self.blink_started)
+            .as_millis();
+        Some(Duration::from_millis(
+            (interval - since_start % interval) as u64,
+        ))
+    }
+
+    /// Show the current frame of every running animation
+    fn animate(&mut self, now: Instant) {
+        for (window, animation) in self.windows.iter_mut().zip(self.animations.iter()) {
+            if let Some(animation) = animation {
+                window.displays = animation.frames[animation.frame_index(now)];
+            }
+        }
+    }
+
+    /// How long until the next animation frame, None if nothing is animated
+    fn until_next_frame(&self, now: Instant) -> Option<Duration> {
+        let interval = ANIMATION_FRAME_INTERVAL.as_millis();
+        self.animations
+            .iter()
+            .flatten()
+            .map(|animation| {
+                let since_start = now.saturating_duration_since(animation.started).as_millis();
+                Duration::from_millis((interval - since_start % interval) as u64)
+            })
+            .min()
+    }
+
+    fn encode_report(&self, now: Instant) -> [u8; CONTROL_MESSAGE_SIZE] {
         let mut output_buffer = [DIGIT_BLANK; CONTROL_MESSAGE_SIZE];
+        let blink_phase_on = self.blink_phase_on(now);
 
         // Turn stored data into data buffer to send to device
         // Encoded like this (x meaning irrelevant)
@@ -184,46 +444,144 @@ impl RadioPanel {
         // 1110xxxx Shows dash/minus
         for (window_index, window) in self.windows.iter().enumerate() {
             for (display_index, display) in window.displays.iter().enumerate() {
-                output_buffer[(5 * window_index) + (display_index + 1)] = display.get_data();
+                let hidden = !blink_phase_on && (window.blinking || display.blinking);
+                output_buffer[(5 * window_index) + (display_index + 1)] = if hidden {
+                    DIGIT_BLANK
+                } else {
+                    display.get_data()
+                };
                 // I don't know why the display_index has to be offset by 1
             }
         }
 
-        // Send to hardware to display
         output_buffer[0] = 0; // I don't know why this is required
-        self.hid_device.send_feature_report(&output_buffer).unwrap();
+        output_buffer
+    }
+
+    /// Send to hardware to display
+    fn send_report(
+        &mut self,
+        output_buffer: [u8; CONTROL_MESSAGE_SIZE],
+    ) -> Result<(), RadioPanelError> {
+        match self.transport.write_feature_report(&output_buffer) {
+            Err(RadioPanelError::Disconnected) => {
+                self.handle_disconnect();
+                Ok(())
+            }
+            Err(error) => {
+                self.last_sent_report = None;
+                Err(error)
+            }
+            Ok(()) => {
+                self.last_sent_report = Some(output_buffer);
+                self.display_stats.reports_sent += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// The device underneath, e.g. to queue input reports on a mock
+    #[cfg(test)]
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// What a window was set to, as it would be passed to set_window, blinking aside
+    #[cfg(test)]
+    pub fn window_text(&self, window: Window) -> String {
+        let mut text = String::new();
+        for display in self.windows[window as usize].displays {
+            text.push(decode_glyph(display.value).unwrap_or('?'));
+            if display.has_decimal_point {
+                text.push('.');
+            }
+        }
+        text
+    }
+
+    pub fn clear_all_windows(&mut self) -> Result<(), RadioPanelError> {
+        self.set_window(Window::TopLeft, "     ")?;
+        self.set_window(Window::TopRight, "     ")?;
+        self.set_window(Window::BottomLeft, "     ")?;
+        self.set_window(Window::BottomRight, "     ")?;
+        self.update_all_windows()
+    }
+
+    fn handle_disconnect(&mut self) {
+        println!("Lost connection to hardware. Waiting for it to be plugged back in...");
+        self.connected = false;
+        self.last_sent_report = None; // the hardware forgets everything when unplugged
+    }
+
+    /// Look for the unplugged device once
+    /// When it is back, restore what was shown on the displays before
+    fn try_reconnect(&mut self) -> Result<bool, RadioPanelError> {
+        if !self.transport.reconnect(self.input_timeout_ms)? {
+            return Ok(false);
+        }
+
+        println!("Reconnected to hardware");
+        self.connected = true;
+        self.force_update_all_windows()?;
+        Ok(self.connected) // could have been lost again right away
     }
+}
+
+/// Turns a value into the contents of the 5 7-segment displays of a window
+/// Unused displays on the right stay blank
+fn encode_window(
+    value: &str,
+) -> Result<[SevenSegmentDisplay; DEVICE_SEVEN_SEGMENT_COUNT], RadioPanelError> {
+    let mut displays = [SevenSegmentDisplay {
+        value: DIGIT_BLANK,
+        has_decimal_point: false,
+        blinking: false,
+    }; DEVICE_SEVEN_SEGMENT_COUNT];
+    let mut display_index = 0;
+
+    for character in value.chars() {
+        // If a dot succeeds a digit, tell that previous digit it has a decimal point
+        if character == '.' {
+            if display_index == 0 {
+                return Err(RadioPanelError::InvalidGlyph(character));
+            }
+            displays[display_index - 1].has_decimal_point = true;
+            continue;
+        }
+
+        if display_index == DEVICE_SEVEN_SEGMENT_COUNT {
+            return Err(RadioPanelError::ValueTooLong(value.to_string()));
+        }
 
-    pub fn clear_all_windows(&mut self) {
-        self.set_window(Window::TopLeft, "     ");
-        self.set_window(Window::TopRight, "     ");
-        self.set_window(Window::BottomLeft, "     ");
-        self.set_window(Window::BottomRight, "     ");
-        self.update_all_windows();
+        displays[display_index].value = encode_glyph(character)?;
+
+        display_index += 1;
     }
+
+    Ok(displays)
 }
 
 /// Returns what state is a mode selector is in
-fn parse_mode_selector_state(input_buffer: u32, bitmaps: ModeSelectorBitmaps) -> ModeSelectorState {
+fn parse_mode_selector_state(
+    input_buffer: u32,
+    bitmaps: ModeSelectorBitmaps,
+) -> Result<ModeSelectorState, RadioPanelError> {
     if bitmask_applies(input_buffer, bitmaps.com1) {
-        ModeSelectorState::ModeSelectorCom1
+        Ok(ModeSelectorState::ModeSelectorCom1)
     } else if bitmask_applies(input_buffer, bitmaps.com2) {
-        ModeSelectorState::ModeSelectorCom2
+        Ok(ModeSelectorState::ModeSelectorCom2)
     } else if bitmask_applies(input_buffer, bitmaps.nav1) {
-        ModeSelectorState::ModeSelectorNav1
+        Ok(ModeSelectorState::ModeSelectorNav1)
     } else if bitmask_applies(input_buffer, bitmaps.nav2) {
-        ModeSelectorState::ModeSelectorNav2
+        Ok(ModeSelectorState::ModeSelectorNav2)
     } else if bitmask_applies(input_buffer, bitmaps.adf) {
-        ModeSelectorState::ModeSelectorAdf
+        Ok(ModeSelectorState::ModeSelectorAdf)
     } else if bitmask_applies(input_buffer, bitmaps.dme) {
-        ModeSelectorState::ModeSelectorDme
+        Ok(ModeSelectorState::ModeSelectorDme)
     } else if bitmask_applies(input_buffer, bitmaps.xpdr) {
-        ModeSelectorState::ModeSelectorXpdr
+        Ok(ModeSelectorState::ModeSelectorXpdr)
     } else {
-        panic!(
-            "Error in input mask parsing. Unknown bitmask: {:#034b}",
-            input_buffer
-        )
+        Err(RadioPanelError::MalformedReport(input_buffer))
     }
 }
 
@@ -258,3 +616,342 @@ fn parse_button_state(input_buffer: u32, bitmask_button_pressed: u32) -> ButtonS
 fn bitmask_applies(input_buffer: u32, bitmask: u32) -> bool {
     (input_buffer & bitmask) > 0
 }
+
+#[cfg(test)]
+mod device_tests {
+    use super::*;
+    use crate::radio_panel::transport::MockTransport;
+
+    fn mock_panel() -> RadioPanel<MockTransport> {
+        RadioPanel::with_transport(MockTransport::new())
+    }
+
+    #[test]
+    fn test_timeout_returns_no_input() {
+        let mut radio_panel = mock_panel();
+        assert!(radio_panel.block_until_input().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_input_report_parsing() {
+        let mut radio_panel = mock_panel();
+        // upper selector on NAV1, lower selector on XPDR, upper button pressed, lower inner rotary turned
+        radio_panel
+            .transport
+            .push_input_report([0b0000_0100, 0b0110_0000, 0b0001_0000]);
+
+        let input = radio_panel.block_until_input().unwrap().unwrap();
+        assert_eq!(
+            input.mode_selector_upper,
+            ModeSelectorState::ModeSelectorNav1
+        );
+        assert_eq!(
+            input.mode_selector_lower,
+            ModeSelectorState::ModeSelectorXpdr
+        );
+        assert!(matches!(input.button_upper, ButtonState::Pressed));
+        assert!(matches!(input.button_lower, ButtonState::Released));
+        assert!(matches!(input.rotary_lower_inner, RotaryState::Clockwise));
+        assert!(matches!(input.rotary_upper_inner, RotaryState::None));
+    }
+
+    #[test]
+    fn test_set_window_encoding() {
+        let mut radio_panel = mock_panel();
+        radio_panel.set_window(Window::TopLeft, "123.45").unwrap();
+        radio_panel.set_window(Window::BottomRight, "-  9").unwrap();
+        radio_panel.update_all_windows().unwrap();
+
+        let report = radio_panel.transport.feature_reports[0];
+        assert_eq!(report[0], 0);
+        assert_eq!(report[1..6], [1, 2, 3 | BITMASK_SHOW_DECIMAL_POINT, 4, 5]);
+        assert_eq!(report[6..16], [DIGIT_BLANK; 10]);
+        assert_eq!(
+            report[16..21],
+            [DIGIT_DASH, DIGIT_BLANK, DIGIT_BLANK, 9, DIGIT_BLANK]
+        );
+    }
+
+    #[test]
+    fn test_update_all_windows_sends_every_window() {
+        let mut radio_panel = mock_panel();
+        radio_panel.set_window(Window::TopLeft, "11111").unwrap();
+        radio_panel.set_window(Window::TopRight, "22222").unwrap();
+        radio_panel.set_window(Window::BottomLeft, "33333").unwrap();
+        radio_panel
+            .set_window(Window::BottomRight, "44444")
+            .unwrap();
+        radio_panel.update_all_windows().unwrap();
+        radio_panel.clear_all_windows().unwrap();
+
+        let reports = &radio_panel.transport.feature_reports;
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0][1..6], [1; 5]);
+        assert_eq!(reports[0][6..11], [2; 5]);
+        assert_eq!(reports[0][11..16], [3; 5]);
+        assert_eq!(reports[0][16..21], [4; 5]);
+        assert_eq!(reports[1][1..21], [DIGIT_BLANK; 20]);
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let mut radio_panel = mock_panel();
+        radio_panel.set_window(Window::TopLeft, "12345").unwrap();
+
+        assert!(matches!(
+            radio_panel.set_window(Window::TopLeft, "1x3"),
+            Err(RadioPanelError::InvalidGlyph('x'))
+        ));
+        assert!(matches!(
+            radio_panel.set_window(Window::TopLeft, ".1"),
+            Err(RadioPanelError::InvalidGlyph('.'))
+        ));
+        assert!(matches!(
+            radio_panel.set_window(Window::TopLeft, "123456"),
+            Err(RadioPanelError::ValueTooLong(_))
+        ));
+
+        // previous contents stay untouched
+        radio_panel.update_all_windows().unwrap();
+        assert_eq!(
+            radio_panel.transport.feature_reports[0][1..6],
+            [1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_malformed_report() {
+        let mut radio_panel = mock_panel();
+        // no mode selector bit set at all
+        radio_panel.transport.push_input_report([0, 0, 0b0000_0001]);
+        assert!(matches!(
+            radio_panel.block_until_input(),
+            Err(RadioPanelError::MalformedReport(1))
+        ));
+    }
+
+    #[test]
+    fn test_reconnect_restores_displays() {
+        let mut radio_panel = mock_panel();
+        radio_panel.set_window(Window::TopLeft, "12345").unwrap();
+        radio_panel
+            .set_window(Window::BottomRight, "678.90")
+            .unwrap();
+        radio_panel.update_all_windows().unwrap();
+        let shown_before_unplugging = radio_panel.transport.feature_reports[0];
+
+        radio_panel.transport.unplugged = true;
+        assert!(radio_panel.block_until_input().unwrap().is_none());
+        assert!(!radio_panel.connected);
+
+        // changes while unplugged are kept, not sent
+        radio_panel.set_window(Window::TopRight, "4").unwrap();
+        radio_panel.update_all_windows().unwrap();
+        radio_panel.set_window(Window::TopRight, "     ").unwrap();
+        assert!(radio_panel.block_until_input().unwrap().is_none());
+        assert_eq!(radio_panel.transport.reconnect_attempts, 1);
+        assert_eq!(radio_panel.transport.feature_reports.len(), 1);
+
+        radio_panel.transport.unplugged = false;
+        radio_panel.transport.push_input_report([0b1000_0001, 0, 0]);
+        let input = radio_panel.block_until_input().unwrap().unwrap();
+        assert!(radio_panel.connected);
+        assert_eq!(
+            input.mode_selector_upper,
+            ModeSelectorState::ModeSelectorCom1
+        );
+
+        let reports = &radio_panel.transport.feature_reports;
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1], shown_before_unplugging);
+    }
+
+    #[test]
+    fn test_write_failure_starts_reconnecting() {
+        let mut radio_panel = mock_panel();
+        radio_panel.transport.unplugged = true;
+        radio_panel.set_window(Window::TopLeft, "1").unwrap();
+
+        assert!(radio_panel.update_all_windows().is_ok());
+        assert!(!radio_panel.connected);
+
+        radio_panel.transport.unplugged = false;
+        assert!(radio_panel.block_until_input().unwrap().is_none());
+        assert!(radio_panel.connected);
+        assert_eq!(radio_panel.transport.feature_reports.len(), 1);
+        assert_eq!(radio_panel.transport.feature_reports[0][1], 1);
+    }
+
+    #[test]
+    fn test_events_fire_once_per_action() {
+        let mut radio_panel = mock_panel();
+        let com1_selected = 0b1000_0001;
+        // press and hold the upper button while turning the upper inner knob, then release it
+        radio_panel
+            .transport
+            .push_input_report([com1_selected, 0b0100_0000, 0]);
+        radio_panel
+            .transport
+            .push_input_report([com1_selected, 0b0100_0000, 0b0000_0001]);
+        radio_panel
+            .transport
+            .push_input_report([com1_selected, 0, 0]);
+
+        assert_eq!(
+            radio_panel.block_until_events().unwrap(),
+            vec![InputEvent::ButtonPressed(Button::Upper)]
+        );
+        assert_eq!(
+            radio_panel.block_until_events().unwrap(),
+            vec![InputEvent::RotaryTurned {
+                knob: Knob::UpperInner,
+                direction: Direction::Clockwise
+            }]
+        );
+        assert_eq!(
+            radio_panel.block_until_events().unwrap(),
+            vec![InputEvent::ButtonReleased(Button::Upper)]
+        );
+        assert!(radio_panel.block_until_events().unwrap().is_empty()); // timeout
+    }
+
+    #[test]
+    fn test_unchanged_windows_are_not_resent() {
+        let mut radio_panel = mock_panel();
+        radio_panel.set_window(Window::TopLeft, "12345").unwrap();
+        radio_panel.update_all_windows().unwrap();
+        radio_panel.update_all_windows().unwrap();
+        radio_panel.set_window(Window::TopLeft, "12345").unwrap(); // same value again
+        radio_panel.update_all_windows().unwrap();
+        assert_eq!(radio_panel.transport.feature_reports.len(), 1);
+
+        radio_panel.set_window(Window::TopLeft, "54321").unwrap();
+        radio_panel.update_all_windows().unwrap();
+        radio_panel.force_update_all_windows().unwrap();
+
+        let reports = &radio_panel.transport.feature_reports;
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[1], reports[2]);
+        assert_eq!(
+            radio_panel.display_stats(),
+            DisplayStats {
+                reports_sent: 3,
+                reports_skipped: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_blinking() {
+        let mut radio_panel = mock_panel();
+        radio_panel.set_blink_interval(Duration::from_millis(500));
+        radio_panel.set_window(Window::TopLeft, "12.345").unwrap();
+        radio_panel.set_window(Window::BottomRight, "678").unwrap();
+        radio_panel.set_display_blinking(Window::TopLeft, 1, true);
+        radio_panel.set_window_blinking(Window::BottomRight, true);
+
+        let start = radio_panel.blink_started;
+        let on = radio_panel.encode_report(start + Duration::from_millis(100));
+        let off = radio_panel.encode_report(start + Duration::from_millis(600));
+        let on_again = radio_panel.encode_report(start + Duration::from_millis(1000));
+
+        assert_eq!(on[1..6], [1, 2 | BITMASK_SHOW_DECIMAL_POINT, 3, 4, 5]);
+        assert_eq!(off[1..6], [1, DIGIT_BLANK, 3, 4, 5]);
+        assert_eq!(on[16..19], [6, 7, 8]);
+        assert_eq!(off[16..21], [DIGIT_BLANK; 5]);
+        assert_eq!(on, on_again);
+        assert_eq!(
+            radio_panel.until_next_blink(start + Duration::from_millis(100)),
+            Some(Duration::from_millis(400))
+        );
+
+        // setting a new value stops blinking
+        radio_panel.set_window(Window::TopLeft, "12345").unwrap();
+        radio_panel.set_window(Window::BottomRight, "678").unwrap();
+        assert!(radio_panel.until_next_blink(start).is_none());
+    }
+
+    #[test]
+    fn test_animation() {
+        let mut radio_panel = mock_panel();
+        radio_panel
+            .start_animation(Window::TopRight, &Animation::Sweep)
+            .unwrap();
+        assert!(radio_panel.is_animating());
+
+        let start = radio_panel.animations[Window::TopRight as usize]
+            .as_ref()
+            .unwrap()
+            .started;
+        radio_panel.animate(start + ANIMATION_FRAME_INTERVAL * 2);
+        let report = radio_panel.encode_report(start);
+        assert_eq!(
+            report[6..11],
+            [
+                DIGIT_BLANK,
+                DIGIT_BLANK,
+                DIGIT_DASH,
+                DIGIT_BLANK,
+                DIGIT_BLANK
+            ]
+        );
+        assert_eq!(
+            radio_panel.until_next_frame(start + Duration::from_millis(100)),
+            Some(ANIMATION_FRAME_INTERVAL - Duration::from_millis(100))
+        );
+
+        // setting a value stops the animation of that window only
+        radio_panel
+            .start_animation(Window::BottomLeft, &Animation::Spinner)
+            .unwrap();
+        radio_panel.set_window(Window::TopRight, "1").unwrap();
+        assert!(radio_panel.animations[Window::TopRight as usize].is_none());
+        assert!(radio_panel.is_animating());
+
+        // so does any input
+        radio_panel.transport.push_input_report([0b1000_0001, 0, 0]);
+        radio_panel.block_until_input().unwrap();
+        assert!(!radio_panel.is_animating());
+    }
+
+    #[test]
+    fn test_invalid_animation() {
+        let mut radio_panel = mock_panel();
+        let animation = Animation::Marquee(String::from("ALT 5000"));
+        assert!(matches!(
+            radio_panel.start_animation(Window::TopLeft, &animation),
+            Err(RadioPanelError::InvalidGlyph('A'))
+        ));
+        assert!(!radio_panel.is_animating());
+    }
+
+    #[test]
+    fn test_input_for_event() {
+        let mut input = InputState::new();
+        input.button_upper = ButtonState::Pressed;
+
+        let input = input.with_event(InputEvent::ModeChanged {
+            selector: Selector::Lower,
+            from: ModeSelectorState::ModeSelectorCom1,
+            to: ModeSelectorState::ModeSelectorAdf,
+        });
+        assert!(matches!(input.button_upper, ButtonState::Released));
+        assert_eq!(
+            input.mode_selector_lower,
+            ModeSelectorState::ModeSelectorAdf
+        );
+
+        let input = input.with_event(InputEvent::RotaryTurned {
+            knob: Knob::LowerOuter,
+            direction: Direction::CounterClockwise,
+        });
+        assert!(matches!(
+            input.rotary_lower_outer,
+            RotaryState::CounterClockwise
+        ));
+        assert_eq!(
+            input.mode_selector_lower,
+            ModeSelectorState::ModeSelectorAdf
+        );
+    }
+}
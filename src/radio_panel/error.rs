@@ -0,0 +1,66 @@
+use hidapi::HidError;
+use std::{error::Error, fmt};
+
+/// Everything that can go wrong while talking to the Radio Panel
+#[derive(Debug)]
+pub enum RadioPanelError {
+    /// No Radio Panel is plugged in
+    NotFound,
+    /// The Radio Panel stopped responding, most likely it was unplugged
+    Disconnected,
+    /// The HID library failed for a reason unrelated to the device itself
+    Io(HidError),
+    /// A character the 7-segment displays can't show
+    InvalidGlyph(char),
+    /// More digits than a window has 7-segment displays
+    ValueTooLong(String),
+    /// An input report that doesn't match any known button, rotary or mode selector layout
+    MalformedReport(u32),
+}
+
+impl fmt::Display for RadioPanelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RadioPanelError::NotFound => {
+                write!(f, "Couldn't connect to hardware. Is it plugged in?")
+            }
+            RadioPanelError::Disconnected => write!(f, "Lost connection to hardware"),
+            RadioPanelError::Io(error) => write!(f, "Hardware communication failed: {}", error),
+            RadioPanelError::InvalidGlyph(character) => write!(
+                f,
+                "Impossible value for 7-segement to display: {:?}",
+                character
+            ),
+            RadioPanelError::ValueTooLong(value) => {
+                write!(f, "Value doesn't fit into a window: {:?}", value)
+            }
+            RadioPanelError::MalformedReport(input_buffer) => write!(
+                f,
+                "Error in input mask parsing. Unknown bitmask: {:#034b}",
+                input_buffer
+            ),
+        }
+    }
+}
+
+impl Error for RadioPanelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RadioPanelError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<HidError> for RadioPanelError {
+    /// hidapi reports a vanished device as a plain API error,
+    /// everything else is a problem on our side
+    fn from(error: HidError) -> Self {
+        match error {
+            HidError::HidApiError { .. } | HidError::HidApiErrorEmpty => {
+                RadioPanelError::Disconnected
+            }
+            error => RadioPanelError::Io(error),
+        }
+    }
+}
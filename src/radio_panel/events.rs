@@ -0,0 +1,160 @@
+use super::{device::InputState, hardware::*};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Upper,
+    Lower,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Knob {
+    UpperInner,
+    UpperOuter,
+    LowerInner,
+    LowerOuter,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Selector {
+    Upper,
+    Lower,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// A single physical action on the Radio Panel
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    ButtonPressed(Button),
+    ButtonReleased(Button),
+    ButtonHeld(Button), // still pressed after a long press, only reported once per press
+    ButtonDoublePressed(Button), // right after the second of two quick presses
+    RotaryTurned {
+        knob: Knob,
+        direction: Direction,
+    },
+    ModeChanged {
+        selector: Selector,
+        from: ModeSelectorState,
+        to: ModeSelectorState,
+    },
+}
+
+/// Compare two consecutive input reports and return what happened in between
+/// Buttons and mode selectors report their level, so only changes count.
+/// Rotaries only set their bit in the report of the detent itself, so every report carrying one is a turn.
+pub fn diff_input_states(previous: &InputState, current: &InputState) -> Vec<InputEvent> {
+    let mut events = Vec::new();
+
+    for (selector, from, to) in [
+        (
+            Selector::Upper,
+            previous.mode_selector_upper,
+            current.mode_selector_upper,
+        ),
+        (
+            Selector::Lower,
+            previous.mode_selector_lower,
+            current.mode_selector_lower,
+        ),
+    ] {
+        if from != to {
+            events.push(InputEvent::ModeChanged { selector, from, to });
+        }
+    }
+
+    for (button, was, is) in [
+        (Button::Upper, previous.button_upper, current.button_upper),
+        (Button::Lower, previous.button_lower, current.button_lower),
+    ] {
+        match (was, is) {
+            (ButtonState::Released, ButtonState::Pressed) => {
+                events.push(InputEvent::ButtonPressed(button))
+            }
+            (ButtonState::Pressed, ButtonState::Released) => {
+                events.push(InputEvent::ButtonReleased(button))
+            }
+            _ => (),
+        }
+    }
+
+    for (knob, rotary) in [
+        (Knob::UpperInner, current.rotary_upper_inner),
+        (Knob::UpperOuter, current.rotary_upper_outer),
+        (Knob::LowerInner, current.rotary_lower_inner),
+        (Knob::LowerOuter, current.rotary_lower_outer),
+    ] {
+        let direction = match rotary {
+            RotaryState::Clockwise => Direction::Clockwise,
+            RotaryState::CounterClockwise => Direction::CounterClockwise,
+            RotaryState::None => continue,
+        };
+        events.push(InputEvent::RotaryTurned { knob, direction });
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod event_tests {
+    use super::*;
+
+    #[test]
+    fn test_no_change() {
+        let input = InputState::new();
+        assert!(diff_input_states(&input, &input).is_empty());
+    }
+
+    #[test]
+    fn test_button_edges() {
+        let released = InputState::new();
+        let mut pressed = InputState::new();
+        pressed.button_lower = ButtonState::Pressed;
+
+        assert_eq!(
+            diff_input_states(&released, &pressed),
+            vec![InputEvent::ButtonPressed(Button::Lower)]
+        );
+        assert!(diff_input_states(&pressed, &pressed).is_empty()); // held
+        assert_eq!(
+            diff_input_states(&pressed, &released),
+            vec![InputEvent::ButtonReleased(Button::Lower)]
+        );
+    }
+
+    #[test]
+    fn test_every_detent_is_a_turn() {
+        let mut turning = InputState::new();
+        turning.rotary_upper_outer = RotaryState::CounterClockwise;
+        let turned = InputEvent::RotaryTurned {
+            knob: Knob::UpperOuter,
+            direction: Direction::CounterClockwise,
+        };
+
+        assert_eq!(
+            diff_input_states(&InputState::new(), &turning),
+            vec![turned]
+        );
+        assert_eq!(diff_input_states(&turning, &turning), vec![turned]);
+    }
+
+    #[test]
+    fn test_mode_change() {
+        let previous = InputState::new();
+        let mut current = InputState::new();
+        current.mode_selector_upper = ModeSelectorState::ModeSelectorXpdr;
+
+        assert_eq!(
+            diff_input_states(&previous, &current),
+            vec![InputEvent::ModeChanged {
+                selector: Selector::Upper,
+                from: ModeSelectorState::ModeSelectorCom1,
+                to: ModeSelectorState::ModeSelectorXpdr,
+            }]
+        );
+    }
+}
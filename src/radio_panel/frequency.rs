@@ -1,97 +1,331 @@
-#[derive(Copy, Clone, Debug)]
+use std::{fmt, str::FromStr};
+
+use super::utility::wrap;
+
+/// Range of frequencies a radio tunes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Band {
+    Com, // VHF COM, 118.000 to 136.999 MHz
+    Nav, // VHF NAV, 108.00 to 117.99 MHz
+    Adf, // NDBs, 190 to 1799.5 kHz
+}
+
+impl Band {
+    /// Lowest frequency and the one just above the highest, in Hz
+    fn range(&self) -> (u32, u32) {
+        match self {
+            Band::Com => (118_000_000, 137_000_000),
+            Band::Nav => (108_000_000, 118_000_000),
+            Band::Adf => (190_000, 1_800_000),
+        }
+    }
+
+    /// Finest step a frequency can be set to, in Hz
+    /// COM and NAV in whole kHz, as far as the panel can show them
+    fn resolution(&self) -> u32 {
+        match self {
+            Band::Com | Band::Nav => 1000,
+            Band::Adf => 500,
+        }
+    }
+
+    /// Whether a frequency in Hz lies within the band, ignoring the resolution
+    fn contains(&self, hz: u32) -> bool {
+        let (min, max) = self.range();
+        (min..max).contains(&hz)
+    }
+}
+
+/// A frequency within a band, in Hz
+/// For COM with 8.33 kHz channels this is the channel name, e.g. 118.010 MHz for 118.00833 MHz,
+/// ChannelSpacing knows the frequency behind the name
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Frequency {
-    pub integer: i16,
-    pub fraction: i16,
+    band: Band,
+    hz: u32,
 }
 
-// Formats a given frequency to the 00.000 or 000.00 format
-pub fn format_frequency(freq: Frequency, fractional_digits: u8) -> String {
-    match fractional_digits {
-        2 => {
-            format!(
-                "{integer}.{fraction}",
-                integer = freq.integer,
-                fraction = format!("{:03}", freq.fraction)
-                    .chars()
-                    .take(2)
-                    .collect::<String>()
-            )
+impl Frequency {
+    /// A frequency in Hz, fails outside of the band or between steps of its resolution
+    pub fn new(band: Band, hz: u32) -> Result<Frequency, String> {
+        if !band.contains(hz) {
+            return Err(format!("{} Hz is outside of the {:?} band", hz, band));
+        }
+        if !hz.is_multiple_of(band.resolution()) {
+            return Err(format!(
+                "{} Hz is not a multiple of {} Hz",
+                hz,
+                band.resolution()
+            ));
+        }
+        Ok(Frequency { band, hz })
+    }
+
+    pub fn com(hz: u32) -> Result<Frequency, String> {
+        Frequency::new(Band::Com, hz)
+    }
+
+    pub fn nav(hz: u32) -> Result<Frequency, String> {
+        Frequency::new(Band::Nav, hz)
+    }
+
+    pub fn adf(hz: u32) -> Result<Frequency, String> {
+        Frequency::new(Band::Adf, hz)
+    }
+
+    /// The lowest frequency of a band, e.g. 118.000 MHz for COM
+    pub fn lowest(band: Band) -> Frequency {
+        Frequency {
+            band,
+            hz: band.range().0,
+        }
+    }
+
+    /// The valid frequency closest to any value in Hz, e.g. from a simulator
+    /// Rounds to the band's resolution and stays within the band
+    pub fn closest(band: Band, hz: u32) -> Frequency {
+        let (min, max) = band.range();
+        let resolution = band.resolution();
+        let rounded = (hz + resolution / 2) / resolution * resolution;
+        Frequency {
+            band,
+            hz: rounded.clamp(min, max - resolution),
         }
-        3 => {
-            let integer = freq.integer.to_string()[1..].to_string();
-            let fraction = format!("{:03}", freq.fraction);
-            format!("{integer}.{fraction}",)
+    }
+
+    pub fn band(&self) -> Band {
+        self.band
+    }
+
+    /// The frequency in Hz, e.g. 118.250 MHz is 118250000
+    pub fn hz(&self) -> u32 {
+        self.hz
+    }
+
+    /// Whole MHz, e.g. 118 for 118.250 MHz
+    pub fn mhz(&self) -> u32 {
+        self.hz / 1_000_000
+    }
+
+    /// kHz behind the decimal point, e.g. 250 for 118.250 MHz
+    pub fn fraction(&self) -> i16 {
+        (self.hz % 1_000_000 / 1000) as i16
+    }
+
+    /// Same MHz with other kHz behind the decimal point, wraps around within the MHz
+    pub fn with_fraction(&self, fraction: i16) -> Frequency {
+        let fraction = wrap(fraction, 0, 1000) as u32;
+        Frequency::closest(self.band, self.mhz() * 1_000_000 + fraction * 1000)
+    }
+
+    /// Tuned up or down by some Hz, past the end of the band starts over at the other end
+    pub fn add_hz(&self, delta: i32) -> Frequency {
+        let (min, max) = self.band.range();
+        let hz = wrap(
+            i64::from(self.hz) + i64::from(delta),
+            i64::from(min),
+            i64::from(max),
+        );
+        Frequency::closest(self.band, hz as u32)
+    }
+
+    /// The value SimConnect's BCD events take
+    /// ADF in BCD Hz, e.g. 0x0034_5500 for 345.5 kHz,
+    /// COM and NAV the four digits between the leading 1 and the last kHz, e.g. 0x1825 for 118.25 MHz
+    #[cfg(any(windows, test))]
+    pub fn bcd(&self) -> u32 {
+        let digits = match self.band {
+            Band::Adf => self.hz,
+            Band::Com | Band::Nav => self.hz / 10_000 % 10_000,
+        };
+        let mut bcd = 0;
+        let mut shift = 0;
+        let mut rest = digits;
+        while rest > 0 {
+            bcd |= (rest % 10) << shift;
+            rest /= 10;
+            shift += 4;
         }
-        _ => {
-            panic!("Can't format frequencies other than 2 or 3 digits")
+        bcd
+    }
+}
+
+/// COM and NAV in MHz, e.g. "118.250", ADF in kHz, e.g. "345" or "345.5"
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.band {
+            Band::Com | Band::Nav => write!(f, "{}.{:03}", self.mhz(), self.fraction()),
+            Band::Adf if self.hz.is_multiple_of(1000) => write!(f, "{}", self.hz / 1000),
+            Band::Adf => write!(f, "{}.{}", self.hz / 1000, self.hz % 1000 / 100),
         }
     }
 }
 
+/// The band follows from the value, e.g. "121.5" is COM, "113.9" NAV and "345" ADF
+impl FromStr for Frequency {
+    type Err = String;
+
+    fn from_str(frequency: &str) -> Result<Frequency, String> {
+        let invalid = || format!("Invalid frequency: {:?}", frequency);
+
+        let (whole, decimals) = frequency.split_once('.').unwrap_or((frequency, "0"));
+        let digits = |part: &str, most| {
+            (1..=most).contains(&part.len()) && part.chars().all(|c| c.is_ascii_digit())
+        };
+        if !digits(whole, 4) || !digits(decimals, 3) {
+            return Err(invalid());
+        }
+        // thousandths of the unit written, i.e. kHz when written in MHz and Hz when in kHz
+        let whole: u32 = whole.parse().map_err(|_| invalid())?;
+        let decimals: u32 = format!("{:0<3}", decimals).parse().map_err(|_| invalid())?;
+        let thousandths = whole
+            .checked_mul(1000)
+            .and_then(|whole| whole.checked_add(decimals))
+            .ok_or_else(invalid)?;
+
+        match thousandths.checked_mul(1000) {
+            Some(hz) if Band::Com.contains(hz) => return Frequency::com(hz),
+            Some(hz) if Band::Nav.contains(hz) => return Frequency::nav(hz),
+            _ => {}
+        }
+        if Band::Adf.contains(thousandths) {
+            return Frequency::adf(thousandths);
+        }
+        Err(invalid())
+    }
+}
+
+/// Formats a frequency for a window
+/// COM without the leading 1, e.g. "18.250", NAV with two decimals, e.g. "108.05", ADF in kHz
+pub fn format_frequency(freq: Frequency) -> String {
+    match freq.band() {
+        Band::Com => format!("{:02}.{:03}", freq.mhz() % 100, freq.fraction()),
+        Band::Nav => format!("{}.{:02}", freq.mhz(), freq.fraction() / 10),
+        // the decimal point doesn't take a digit of its own
+        Band::Adf if freq.hz().is_multiple_of(1000) => format!("{:>5}", freq.to_string()),
+        Band::Adf => format!("{:>6}", freq.to_string()),
+    }
+}
+
 #[cfg(test)]
-mod frequency_formatting_tests {
-    use crate::{format_frequency, Frequency};
+mod frequency_tests {
+    use super::*;
+
+    /// Every frequency a band can hold
+    fn every_frequency(band: Band) -> impl Iterator<Item = Frequency> {
+        let (min, max) = band.range();
+        (min..max)
+            .step_by(band.resolution() as usize)
+            .map(move |hz| Frequency::new(band, hz).unwrap())
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(Frequency::com(118_250_000).is_ok());
+        assert!(Frequency::com(136_990_000).is_ok());
+        assert!(Frequency::com(137_000_000).is_err());
+        assert!(Frequency::com(117_975_000).is_err());
+        assert!(Frequency::com(118_008_333).is_err()); // 8.33 kHz channels go by their names
+        assert!(Frequency::nav(117_950_000).is_ok());
+        assert!(Frequency::nav(118_000_000).is_err());
+        assert!(Frequency::adf(345_500).is_ok());
+        assert!(Frequency::adf(345_250).is_err());
+        assert!(Frequency::adf(1_800_000).is_err());
+    }
+
+    #[test]
+    fn test_closest() {
+        let freq = Frequency::closest(Band::Com, 118_008_333);
+        assert_eq!((freq.mhz(), freq.fraction()), (118, 8));
+        assert_eq!(Frequency::closest(Band::Com, 118_999_600).hz(), 119_000_000);
+        assert_eq!(Frequency::closest(Band::Adf, 345_300).hz(), 345_500);
+        // outside of the band
+        assert_eq!(Frequency::closest(Band::Nav, 121_500_000).hz(), 117_999_000);
+        assert_eq!(Frequency::closest(Band::Adf, 0).hz(), 190_000);
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let freq = Frequency::com(136_975_000).unwrap();
+        assert_eq!(freq.add_hz(1_000_000).to_string(), "118.975");
+        assert_eq!(freq.with_fraction(1000).to_string(), "136.000");
+        assert_eq!(freq.with_fraction(-25).to_string(), "136.975");
+
+        let freq = Frequency::nav(108_000_000).unwrap();
+        assert_eq!(freq.add_hz(-1_000_000).to_string(), "117.000");
+
+        let freq = Frequency::adf(1_750_000).unwrap();
+        assert_eq!(freq.add_hz(100_000).to_string(), "240");
+        assert_eq!(freq.add_hz(-500).to_string(), "1749.5");
+    }
+
+    #[test]
+    fn test_every_frequency_round_trips_through_text() {
+        for band in [Band::Com, Band::Nav, Band::Adf] {
+            for freq in every_frequency(band) {
+                assert_eq!(freq.to_string().parse(), Ok(freq));
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_frequency_fits_a_window() {
+        for band in [Band::Com, Band::Nav, Band::Adf] {
+            for freq in every_frequency(band) {
+                let text = format_frequency(freq);
+                // the decimal point shares a digit
+                assert!(text.replace('.', "").len() <= 5, "{:?}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!("121.5".parse(), Frequency::com(121_500_000));
+        assert_eq!("113.90".parse(), Frequency::nav(113_900_000));
+        assert_eq!("345".parse(), Frequency::adf(345_000));
+        assert_eq!("1799.5".parse(), Frequency::adf(1_799_500));
+        for invalid in [
+            "", ".5", "121.", "121.5.0", "12a", "121.0001", "150", "-121.5", "9999.999",
+        ] {
+            assert!(invalid.parse::<Frequency>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_bcd() {
+        assert_eq!(Frequency::com(118_250_000).unwrap().bcd(), 0x1825);
+        assert_eq!(Frequency::nav(110_300_000).unwrap().bcd(), 0x1030);
+        assert_eq!(Frequency::adf(345_500).unwrap().bcd(), 0x0034_5500);
+        assert_eq!(Frequency::adf(1_799_000).unwrap().bcd(), 0x0179_9000);
+    }
 
     #[test]
     fn test_two_digits() {
-        let freq1: Frequency = Frequency {
-            integer: 108,
-            fraction: 0,
-        };
-        let freq2: Frequency = Frequency {
-            integer: 108,
-            fraction: 50,
-        };
-        let freq3: Frequency = Frequency {
-            integer: 108,
-            fraction: 100,
-        };
-        let freq4: Frequency = Frequency {
-            integer: 108,
-            fraction: 150,
-        };
-        let freq5: Frequency = Frequency {
-            integer: 108,
-            fraction: 200,
-        };
-        assert_eq!(format_frequency(freq1, 2), "108.00");
-        assert_eq!(format_frequency(freq2, 2), "108.05");
-        assert_eq!(format_frequency(freq3, 2), "108.10");
-        assert_eq!(format_frequency(freq4, 2), "108.15");
-        assert_eq!(format_frequency(freq5, 2), "108.20");
+        let format = |hz| format_frequency(Frequency::nav(hz).unwrap());
+        assert_eq!(format(108_000_000), "108.00");
+        assert_eq!(format(108_050_000), "108.05");
+        assert_eq!(format(108_100_000), "108.10");
+        assert_eq!(format(108_150_000), "108.15");
+        assert_eq!(format(108_200_000), "108.20");
     }
 
     #[test]
     fn test_three_digits() {
-        let freq1: Frequency = Frequency {
-            integer: 108,
-            fraction: 0,
-        };
-        let freq2: Frequency = Frequency {
-            integer: 108,
-            fraction: 50,
-        };
-        let freq3: Frequency = Frequency {
-            integer: 108,
-            fraction: 100,
-        };
-        let freq4: Frequency = Frequency {
-            integer: 108,
-            fraction: 150,
-        };
-        let freq5: Frequency = Frequency {
-            integer: 108,
-            fraction: 200,
-        };
-        let freq6: Frequency = Frequency {
-            integer: 108,
-            fraction: 205,
-        };
-        assert_eq!(format_frequency(freq1, 3), "08.000");
-        assert_eq!(format_frequency(freq2, 3), "08.050");
-        assert_eq!(format_frequency(freq3, 3), "08.100");
-        assert_eq!(format_frequency(freq4, 3), "08.150");
-        assert_eq!(format_frequency(freq5, 3), "08.200");
-        assert_eq!(format_frequency(freq6, 3), "08.205");
+        let format = |hz| format_frequency(Frequency::com(hz).unwrap());
+        assert_eq!(format(118_000_000), "18.000");
+        assert_eq!(format(118_050_000), "18.050");
+        assert_eq!(format(118_100_000), "18.100");
+        assert_eq!(format(118_150_000), "18.150");
+        assert_eq!(format(118_200_000), "18.200");
+        assert_eq!(format(118_205_000), "18.205");
+    }
+
+    #[test]
+    fn test_adf() {
+        let format = |hz| format_frequency(Frequency::adf(hz).unwrap());
+        assert_eq!(format(345_000), "  345");
+        assert_eq!(format(1_750_000), " 1750");
+        assert_eq!(format(345_500), " 345.5");
     }
 }
@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::events::*;
+
+/// How long a button has to stay pressed to count as a long press
+pub const LONG_PRESS_DURATION: Duration = Duration::from_millis(800);
+
+/// Longest time between two presses of a double press
+pub const DOUBLE_PRESS_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Sits between the hardware events and the instruments, like the RotaryAccelerator,
+/// adding a ButtonHeld event once a button is pressed long enough
+/// and a ButtonDoublePressed event after the second of two quick presses
+/// Long presses are only noticed when the panel is polled, so at most an input timeout late
+pub struct GestureTracker {
+    long_press_duration: Duration,
+    double_press_interval: Duration,
+    pressed_since: HashMap<Button, (Instant, bool)>, // and whether reported as held yet
+    previous_presses: HashMap<Button, Instant>,      // that could start a double press
+}
+
+impl GestureTracker {
+    pub fn new(long_press_duration: Duration, double_press_interval: Duration) -> GestureTracker {
+        GestureTracker {
+            long_press_duration,
+            double_press_interval,
+            pressed_since: HashMap::new(),
+            previous_presses: HashMap::new(),
+        }
+    }
+
+    /// Returns the events passed in, with ButtonDoublePressed right after the press completing one,
+    /// followed by ButtonHeld for every button held long enough
+    /// Both presses of a double press are passed on as well, so single presses act without delay
+    pub fn track(&mut self, events: Vec<InputEvent>, time: Instant) -> Vec<InputEvent> {
+        let mut tracked = Vec::new();
+        for event in events {
+            tracked.push(event);
+            match event {
+                InputEvent::ButtonPressed(button) => {
+                    self.pressed_since.insert(button, (time, false));
+                    match self.previous_presses.remove(&button) {
+                        Some(previous)
+                            if time.saturating_duration_since(previous)
+                                <= self.double_press_interval =>
+                        {
+                            tracked.push(InputEvent::ButtonDoublePressed(button))
+                        }
+                        _ => {
+                            self.previous_presses.insert(button, time);
+                        }
+                    }
+                }
+                InputEvent::ButtonReleased(button) => {
+                    self.pressed_since.remove(&button);
+                }
+                _ => (),
+            }
+        }
+
+        let mut events = tracked;
+        for (button, (since, reported)) in self.pressed_since.iter_mut() {
+            if !*reported && time.saturating_duration_since(*since) >= self.long_press_duration {
+                *reported = true;
+                self.previous_presses.remove(button); // a long press doesn't start a double press
+                events.push(InputEvent::ButtonHeld(*button));
+            }
+        }
+        events
+    }
+}
+
+impl Default for GestureTracker {
+    fn default() -> GestureTracker {
+        GestureTracker::new(LONG_PRESS_DURATION, DOUBLE_PRESS_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod gesture_tests {
+    use super::*;
+
+    #[test]
+    fn test_short_press() {
+        let mut tracker = GestureTracker::default();
+        let start = Instant::now();
+
+        let pressed = vec![InputEvent::ButtonPressed(Button::Upper)];
+        assert_eq!(tracker.track(pressed.clone(), start), pressed);
+        let released = vec![InputEvent::ButtonReleased(Button::Upper)];
+        assert_eq!(
+            tracker.track(released.clone(), start + Duration::from_millis(300)),
+            released
+        );
+        assert!(tracker
+            .track(Vec::new(), start + Duration::from_secs(2))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_long_press_is_reported_once() {
+        let mut tracker = GestureTracker::default();
+        let start = Instant::now();
+
+        tracker.track(vec![InputEvent::ButtonPressed(Button::Lower)], start);
+        assert!(tracker
+            .track(Vec::new(), start + Duration::from_millis(500))
+            .is_empty());
+        assert_eq!(
+            tracker.track(Vec::new(), start + Duration::from_millis(900)),
+            vec![InputEvent::ButtonHeld(Button::Lower)]
+        );
+        assert!(tracker
+            .track(Vec::new(), start + Duration::from_secs(3))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_double_press() {
+        let mut tracker = GestureTracker::default();
+        let start = Instant::now();
+        let press = |tracker: &mut GestureTracker, milliseconds| {
+            let time = start + Duration::from_millis(milliseconds);
+            let events = vec![
+                InputEvent::ButtonPressed(Button::Upper),
+                InputEvent::ButtonReleased(Button::Upper),
+            ];
+            tracker.track(events, time)
+        };
+
+        assert_eq!(press(&mut tracker, 0).len(), 2);
+        assert_eq!(
+            press(&mut tracker, 300),
+            vec![
+                InputEvent::ButtonPressed(Button::Upper),
+                InputEvent::ButtonDoublePressed(Button::Upper),
+                InputEvent::ButtonReleased(Button::Upper),
+            ]
+        );
+        // a third press starts over
+        assert_eq!(press(&mut tracker, 600).len(), 2);
+        // too slow
+        assert_eq!(press(&mut tracker, 1100).len(), 2);
+    }
+
+    #[test]
+    fn test_buttons_are_tracked_separately() {
+        let mut tracker = GestureTracker::default();
+        let start = Instant::now();
+
+        tracker.track(vec![InputEvent::ButtonPressed(Button::Upper)], start);
+        let lower_pressed = start + Duration::from_millis(600);
+        tracker.track(
+            vec![InputEvent::ButtonPressed(Button::Lower)],
+            lower_pressed,
+        );
+        assert_eq!(
+            tracker.track(Vec::new(), start + Duration::from_millis(900)),
+            vec![InputEvent::ButtonHeld(Button::Upper)]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use super::{
+    constants::{DIGIT_BLANK, DIGIT_DASH},
+    error::RadioPanelError,
+};
+
+// Every code a 7-segment display accepts
+// 0000_0000 - 0000_1001 Binary encoded decimal, shows 0 to 9
+// 0000_1111             Blank
+// 1101_xxxx             Digit xxxx with a decimal point right of it
+// 1110_xxxx             Dash, regardless of xxxx
+// The firmware decodes digits itself, single segments can't be set.
+// So letters like A, F, L, T or r can't be shown at all.
+
+/// Every character the 7-segment displays can show and its code
+/// Letters only where they look exactly like a digit on a 7-segment display
+pub const GLYPHS: [(char, u8); 21] = [
+    ('0', 0),
+    ('1', 1),
+    ('2', 2),
+    ('3', 3),
+    ('4', 4),
+    ('5', 5),
+    ('6', 6),
+    ('7', 7),
+    ('8', 8),
+    ('9', 9),
+    (' ', DIGIT_BLANK),
+    ('-', DIGIT_DASH),
+    ('O', 0),
+    ('o', 0),
+    ('I', 1),
+    ('l', 1),
+    ('Z', 2),
+    ('S', 5),
+    ('s', 5),
+    ('B', 8),
+    ('g', 9),
+];
+
+/// Returns the code showing a character on a 7-segment display
+pub fn encode_glyph(character: char) -> Result<u8, RadioPanelError> {
+    GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == character)
+        .map(|(_, code)| *code)
+        .ok_or(RadioPanelError::InvalidGlyph(character))
+}
+
+/// Returns the character a code shows, digits rather than their lookalikes
+#[cfg(test)]
+pub fn decode_glyph(code: u8) -> Option<char> {
+    GLYPHS
+        .iter()
+        .find(|(_, glyph_code)| *glyph_code == code)
+        .map(|(glyph, _)| *glyph)
+}
+
+#[cfg(test)]
+mod glyph_tests {
+    use super::*;
+
+    #[test]
+    fn test_digits() {
+        for digit in 0..=9 {
+            let character = char::from_digit(digit, 10).unwrap();
+            assert_eq!(encode_glyph(character).unwrap(), digit as u8);
+        }
+    }
+
+    #[test]
+    fn test_lookalikes() {
+        assert_eq!(encode_glyph('O').unwrap(), encode_glyph('0').unwrap());
+        assert_eq!(encode_glyph('S').unwrap(), encode_glyph('5').unwrap());
+        assert_eq!(encode_glyph('B').unwrap(), encode_glyph('8').unwrap());
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode_glyph(5), Some('5'));
+        assert_eq!(decode_glyph(DIGIT_DASH), Some('-'));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        for character in ['A', 'D', 'F', 'L', 'T', 'r', '.', '#'] {
+            assert!(matches!(
+                encode_glyph(character),
+                Err(RadioPanelError::InvalidGlyph(c)) if c == character
+            ));
+        }
+    }
+}
@@ -6,6 +6,8 @@ use super::constants::{
 pub enum ButtonState {
     Pressed,
     Released,
+    Held,          // long press, never read from the hardware itself
+    DoublePressed, // second of two quick presses, neither read from the hardware
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -15,7 +17,7 @@ pub enum RotaryState {
     CounterClockwise,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ModeSelectorState {
     ModeSelectorCom1,
     ModeSelectorCom2,
@@ -49,12 +51,14 @@ pub enum Window {
 /// Each window has 5, 7-segement displays
 pub struct RadioPanelWindow {
     pub displays: [SevenSegmentDisplay; DEVICE_SEVEN_SEGMENT_COUNT],
+    pub blinking: bool, // whole window turns on and off
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct SevenSegmentDisplay {
-    pub value: u8,               // can only display from 0 to 9 or a dash
+    pub value: u8,               // code from the glyph table
     pub has_decimal_point: bool, // used to show a decimal point right of number
+    pub blinking: bool,          // digit and point turn on and off
 }
 
 impl SevenSegmentDisplay {
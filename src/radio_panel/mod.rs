@@ -1,6 +1,16 @@
+pub mod acceleration;
+pub mod animation;
+pub mod assignment;
 pub mod constants;
 pub mod device;
+pub mod error;
+pub mod events;
 pub mod frequency;
+pub mod gestures;
+pub mod glyphs;
 pub mod hardware;
+pub mod pages;
+pub mod spacing;
 pub mod states;
+pub mod transport;
 pub mod utility;
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, str::FromStr};
+
+use super::{assignment::parse_mode, hardware::ModeSelectorState};
+
+/// What the windows show with both mode selectors in the same position
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Page {
+    Autopilot,         // speed, heading, altitude and vertical speed
+    Courses,           // courses of NAV1 and NAV2
+    Barometer,         // altimeter setting in hPa and inHg
+    MachAndFlightPath, // Mach number and flight path angle
+}
+
+/// Which page each mode selector position shows when both selectors are in it
+/// Positions without a page of their own show the autopilot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageRegistry {
+    pages: HashMap<ModeSelectorState, Page>,
+}
+
+impl PageRegistry {
+    pub fn page(&self, position: ModeSelectorState) -> Page {
+        self.pages
+            .get(&position)
+            .copied()
+            .unwrap_or(Page::Autopilot)
+    }
+}
+
+impl Default for PageRegistry {
+    fn default() -> PageRegistry {
+        PageRegistry {
+            pages: HashMap::from([
+                (ModeSelectorState::ModeSelectorCom1, Page::Autopilot),
+                (ModeSelectorState::ModeSelectorNav1, Page::Courses),
+                (ModeSelectorState::ModeSelectorAdf, Page::Barometer),
+                (ModeSelectorState::ModeSelectorXpdr, Page::MachAndFlightPath),
+            ]),
+        }
+    }
+}
+
+impl FromStr for Page {
+    type Err = String;
+
+    fn from_str(page: &str) -> Result<Page, String> {
+        match page.trim().to_lowercase().as_str() {
+            "ap" => Ok(Page::Autopilot),
+            "crs" => Ok(Page::Courses),
+            "baro" => Ok(Page::Barometer),
+            "mach" => Ok(Page::MachAndFlightPath),
+            unknown => Err(format!("Unknown page: {:?}", unknown)),
+        }
+    }
+}
+
+impl FromStr for PageRegistry {
+    type Err = String;
+
+    /// Parse a comma separated list like "nav2=crs,dme=baro", positions not in it keep their default page
+    fn from_str(pages: &str) -> Result<PageRegistry, String> {
+        let mut registry = PageRegistry::default();
+        for entry in pages.split(',') {
+            let (position, page) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Expected <mode>=<page>, got {:?}", entry))?;
+            registry.pages.insert(parse_mode(position)?, page.parse()?);
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod pages_tests {
+    use super::*;
+
+    #[test]
+    fn test_default_pages() {
+        let registry = PageRegistry::default();
+        assert_eq!(
+            registry.page(ModeSelectorState::ModeSelectorNav1),
+            Page::Courses
+        );
+        assert_eq!(
+            registry.page(ModeSelectorState::ModeSelectorDme),
+            Page::Autopilot
+        );
+    }
+
+    #[test]
+    fn test_parse_pages() {
+        let registry: PageRegistry = "nav2=crs, DME=baro,nav1=ap".parse().unwrap();
+        assert_eq!(
+            registry.page(ModeSelectorState::ModeSelectorNav2),
+            Page::Courses
+        );
+        assert_eq!(
+            registry.page(ModeSelectorState::ModeSelectorDme),
+            Page::Barometer
+        );
+        assert_eq!(
+            registry.page(ModeSelectorState::ModeSelectorNav1),
+            Page::Autopilot
+        );
+        assert_eq!(
+            registry.page(ModeSelectorState::ModeSelectorXpdr),
+            Page::MachAndFlightPath
+        );
+
+        assert!("nav2".parse::<PageRegistry>().is_err());
+        assert!("nav2=vor".parse::<PageRegistry>().is_err());
+        assert!("tacan=crs".parse::<PageRegistry>().is_err());
+    }
+}
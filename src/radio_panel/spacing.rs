@@ -0,0 +1,244 @@
+use std::str::FromStr;
+
+use super::{
+    frequency::{Band, Frequency},
+    utility::wrap,
+};
+
+/// Distance between the channels a radio can be tuned to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChannelSpacing {
+    Khz50, // NAV
+    Khz25, // COM
+    /// COM in Europe, the channel names differ from the frequencies they stand for
+    /// e.g. 118.010 is 118.00833 MHz
+    Khz8_33,
+}
+
+/// What 8.33 kHz channel names ending in 0, 5, 10 and 15 add to their 25 kHz block, in Hz
+/// A name ending in 0 is the old 25 kHz channel, ending in 5 the 8.33 kHz one on the same frequency
+const KHZ_8_33_OFFSETS_HZ: [u32; 4] = [0, 0, 8_333, 16_667];
+
+impl ChannelSpacing {
+    /// Every channel name within a MHz, as the kHz behind the decimal point, e.g. 10 for .010
+    pub fn channels(&self) -> Vec<i16> {
+        match self {
+            ChannelSpacing::Khz50 => (0..1000).step_by(50).collect(),
+            ChannelSpacing::Khz25 => (0..1000).step_by(25).collect(),
+            ChannelSpacing::Khz8_33 => (0..1000)
+                .step_by(25)
+                .flat_map(|block| [block, block + 5, block + 10, block + 15])
+                .collect(),
+        }
+    }
+
+    /// The channel a number of steps away from a fraction, wraps around within the MHz
+    /// A fraction between channels, e.g. set in the simulator, counts as the channel it passes first
+    pub fn step(&self, fraction: i16, steps: i16) -> i16 {
+        let channels = self.channels();
+        let index = match channels.iter().position(|channel| *channel == fraction) {
+            Some(index) => index as i16,
+            None => {
+                let next = channels
+                    .iter()
+                    .position(|channel| *channel > fraction)
+                    .unwrap_or(channels.len()) as i16;
+                if steps > 0 {
+                    next - 1
+                } else {
+                    next
+                }
+            }
+        };
+        channels[wrap(index + steps, 0, channels.len() as i16) as usize]
+    }
+
+    /// The frequency a channel name stands for, in Hz
+    pub fn hz(&self, frequency: Frequency) -> u32 {
+        match self {
+            ChannelSpacing::Khz8_33 => {
+                let block = frequency.fraction() / 25 * 25;
+                let offset =
+                    KHZ_8_33_OFFSETS_HZ[((frequency.fraction() - block) / 5).min(3) as usize];
+                frequency.mhz() * 1_000_000 + block as u32 * 1000 + offset
+            }
+            _ => frequency.hz(),
+        }
+    }
+
+    /// The highest channel of the band, e.g. 136.975 MHz with 25 kHz spacing
+    pub fn highest(&self) -> Frequency {
+        let band = match self {
+            ChannelSpacing::Khz50 => Band::Nav,
+            ChannelSpacing::Khz25 | ChannelSpacing::Khz8_33 => Band::Com,
+        };
+        let last = *self.channels().last().unwrap();
+        // a kHz below the lowest frequency wraps around to the top of the band
+        Frequency::lowest(band).add_hz(-1000).with_fraction(last)
+    }
+
+    /// The channel name closest to a frequency in Hz, above the band its highest channel
+    pub fn frequency_from_hz(&self, hz: u32) -> Frequency {
+        let name = match self {
+            ChannelSpacing::Khz8_33 => {
+                let within_mhz = hz % 1_000_000;
+                let block = (within_mhz / 25_000 * 25) as i16;
+                let in_block = within_mhz % 25_000;
+                // the start of the next block is a 25 kHz channel as well
+                let (_, name) = [(0, 0), (8_333, 10), (16_667, 15), (25_000, 25)]
+                    .into_iter()
+                    .min_by_key(|(offset, _)| in_block.abs_diff(*offset))
+                    .unwrap();
+                // a fraction of 1000 carries over into the next MHz
+                let name_hz = hz / 1_000_000 * 1_000_000 + (block + name) as u32 * 1000;
+                Frequency::closest(Band::Com, name_hz)
+            }
+            ChannelSpacing::Khz25 => Frequency::closest(Band::Com, round_to(hz, 25_000)),
+            ChannelSpacing::Khz50 => Frequency::closest(Band::Nav, round_to(hz, 50_000)),
+        };
+        let highest = self.highest();
+        if name.hz() > highest.hz() {
+            highest
+        } else {
+            name
+        }
+    }
+}
+
+/// The closest multiple of step_hz, halfway rounds up
+fn round_to(hz: u32, step_hz: u32) -> u32 {
+    (hz + step_hz / 2) / step_hz * step_hz
+}
+
+/// "25" or "8.33", the spacings a COM radio can have
+impl FromStr for ChannelSpacing {
+    type Err = String;
+
+    fn from_str(spacing: &str) -> Result<ChannelSpacing, String> {
+        match spacing {
+            "25" => Ok(ChannelSpacing::Khz25),
+            "8.33" => Ok(ChannelSpacing::Khz8_33),
+            _ => Err(format!(
+                "Invalid channel spacing: {:?}, use 25 or 8.33",
+                spacing
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod spacing_tests {
+    use super::*;
+
+    fn frequency(mhz: u32, fraction: u32) -> Frequency {
+        Frequency::com(mhz * 1_000_000 + fraction * 1000).unwrap()
+    }
+
+    #[test]
+    fn test_channel_names() {
+        assert_eq!(ChannelSpacing::Khz50.channels().len(), 20);
+        assert_eq!(ChannelSpacing::Khz25.channels().len(), 40);
+
+        let channels = ChannelSpacing::Khz8_33.channels();
+        assert_eq!(channels.len(), 160);
+        assert_eq!(channels[..9], [0, 5, 10, 15, 25, 30, 35, 40, 50]);
+        // names ending in 20 and 45 don't exist
+        assert!(channels.iter().all(|channel| channel % 25 != 20));
+    }
+
+    #[test]
+    fn test_stepping() {
+        let spacing = ChannelSpacing::Khz8_33;
+        assert_eq!(spacing.step(15, 1), 25);
+        assert_eq!(spacing.step(25, -1), 15);
+        assert_eq!(spacing.step(990, 1), 0);
+        assert_eq!(spacing.step(0, -1), 990);
+        assert_eq!(spacing.step(0, 10), 60);
+
+        // between channels
+        assert_eq!(ChannelSpacing::Khz25.step(8, 1), 25);
+        assert_eq!(ChannelSpacing::Khz25.step(8, -1), 0);
+        assert_eq!(ChannelSpacing::Khz25.step(990, 1), 0);
+    }
+
+    #[test]
+    fn test_8_33_khz_frequencies() {
+        let spacing = ChannelSpacing::Khz8_33;
+        assert_eq!(spacing.hz(frequency(118, 0)), 118_000_000);
+        assert_eq!(spacing.hz(frequency(118, 5)), 118_000_000);
+        assert_eq!(spacing.hz(frequency(118, 10)), 118_008_333);
+        assert_eq!(spacing.hz(frequency(118, 15)), 118_016_667);
+        assert_eq!(spacing.hz(frequency(132, 830)), 132_825_000);
+        assert_eq!(spacing.hz(frequency(136, 990)), 136_991_667);
+    }
+
+    #[test]
+    fn test_every_8_33_khz_channel_round_trips() {
+        let spacing = ChannelSpacing::Khz8_33;
+        for integer in 118..137 {
+            for fraction in spacing.channels() {
+                let name =
+                    spacing.frequency_from_hz(spacing.hz(frequency(integer, fraction as u32)));
+                // 5 is the same frequency as 0, only told apart by the name
+                let expected = if fraction % 25 == 5 {
+                    fraction - 5
+                } else {
+                    fraction
+                };
+                assert_eq!((name.mhz(), name.fraction()), (integer, expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_closest_channel() {
+        let spacing = ChannelSpacing::Khz8_33;
+        // X-Plane reports whole kHz
+        let name = spacing.frequency_from_hz(118_008_000);
+        assert_eq!((name.mhz(), name.fraction()), (118, 10));
+        let name = spacing.frequency_from_hz(118_999_000);
+        assert_eq!((name.mhz(), name.fraction()), (119, 0));
+
+        let name = ChannelSpacing::Khz25.frequency_from_hz(121_500_000);
+        assert_eq!((name.mhz(), name.fraction()), (121, 500));
+    }
+
+    #[test]
+    fn test_off_grid_frequencies_snap_to_a_channel() {
+        let name = ChannelSpacing::Khz25.frequency_from_hz(118_012_000);
+        assert_eq!((name.mhz(), name.fraction()), (118, 0));
+        let name = ChannelSpacing::Khz25.frequency_from_hz(118_013_000);
+        assert_eq!((name.mhz(), name.fraction()), (118, 25));
+        let name = ChannelSpacing::Khz25.frequency_from_hz(118_990_000);
+        assert_eq!((name.mhz(), name.fraction()), (119, 0));
+
+        let name = ChannelSpacing::Khz50.frequency_from_hz(110_520_000);
+        assert_eq!((name.mhz(), name.fraction()), (110, 500));
+        let name = ChannelSpacing::Khz50.frequency_from_hz(110_530_000);
+        assert_eq!((name.mhz(), name.fraction()), (110, 550));
+    }
+
+    #[test]
+    fn test_above_the_band() {
+        assert_eq!(ChannelSpacing::Khz25.highest().to_string(), "136.975");
+        assert_eq!(ChannelSpacing::Khz8_33.highest().to_string(), "136.990");
+        assert_eq!(ChannelSpacing::Khz50.highest().to_string(), "117.950");
+
+        for (spacing, hz, expected) in [
+            (ChannelSpacing::Khz25, 137_500_000, "136.975"),
+            (ChannelSpacing::Khz25, 136_999_000, "136.975"),
+            (ChannelSpacing::Khz8_33, 136_999_000, "136.990"),
+            (ChannelSpacing::Khz8_33, 136_991_667, "136.990"),
+            (ChannelSpacing::Khz50, 118_000_000, "117.950"),
+        ] {
+            assert_eq!(spacing.frequency_from_hz(hz).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!("8.33".parse(), Ok(ChannelSpacing::Khz8_33));
+        assert_eq!("25".parse(), Ok(ChannelSpacing::Khz25));
+        assert!("50".parse::<ChannelSpacing>().is_err());
+    }
+}
@@ -1,4 +1,16 @@
-use crate::radio_panel::frequency::*;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    radio_panel::{
+        frequency::*,
+        gestures::{DOUBLE_PRESS_INTERVAL, LONG_PRESS_DURATION},
+        spacing::ChannelSpacing,
+    },
+    simulator::backend::{AutopilotMode, TransponderMode},
+};
 
 pub struct InstrumentStates {
     pub com1_state: FrequencyState,
@@ -9,25 +21,44 @@ pub struct InstrumentStates {
     pub dme_state: DmeState,
     pub xpdr_state: XpdrState,
     pub autopilot_state: AutopilotState,
+    pub nav1_course: CourseState,
+    pub nav2_course: CourseState,
+    pub barometer_state: BarometerState,
 }
 
 pub struct FrequencyState {
     pub standby_freq: Frequency,
     pub active_freq: Frequency,
+    pub channel_spacing: ChannelSpacing,
 }
 
 pub struct AdfState {
-    pub active_frequency: i16,
-    pub standby_frequency: i16,
+    pub active_frequency: Frequency,
+    pub standby_frequency: Frequency,
+    pub half_khz_steps: bool, // inner knob tunes 0.5 kHz instead of 1 kHz
 }
 
 pub struct DmeState {
     pub distance: Option<f64>,
 }
 
+/// How long after the last turn or press a code being dialed is taken over
+pub const XPDR_COMMIT_DELAY: Duration = Duration::from_secs(3);
+
 pub struct XpdrState {
-    pub code: [i8; 4],
+    pub code: [i8; 4], // what the transponder squawks
     pub selected_digit: usize,
+    pub mode: TransponderMode,
+    /// Being dialed, not squawked until confirmed or left alone for the commit delay,
+    /// so e.g. 7700 on the way from 7000 to 2700 never reaches the transponder
+    pub pending_code: Option<[i8; 4]>,
+    pub last_edit: Instant,
+    pub commit_delay: Duration,
+    /// Pressing past the last digit squawks once the press can't be part of a double or long press anymore
+    pub confirm_at: Option<Instant>,
+    pub confirm_delay: Duration,
+    pub digit_before_press: usize, // back to it when the press turns out to be a long press
+    pub quick_codes: Vec<[i8; 4]>, // recalled in turn with a double press, e.g. 7000 for VFR
 }
 
 pub struct AutopilotState {
@@ -35,7 +66,10 @@ pub struct AutopilotState {
     pub heading: i16,
     pub altitude: i32,
     pub vertical_speed: i16,
+    pub mach: i16,              // hundredths
+    pub flight_path_angle: i16, // tenths of a degree
     pub selected_setting: AutopilotValue,
+    pub engaged: HashSet<AutopilotMode>, // as last known, the simulator has the final say
 }
 
 pub enum AutopilotValue {
@@ -43,63 +77,110 @@ pub enum AutopilotValue {
     VerticalSpeed,
 }
 
+/// The course selected on a NAV radio, and what only NAV radios do with their knobs and button
+pub struct CourseState {
+    pub degrees: i16,
+    pub obs_mode: bool, // the knobs set the course instead of the standby frequency
+    /// A press swaps the frequencies once it can't be the first of a double press anymore,
+    /// which toggles obs_mode instead
+    pub swap_at: Option<Instant>,
+    pub swap_delay: Duration,
+}
+
+pub struct BarometerState {
+    pub pascal: i32,
+}
+
 pub fn instruments_default_state() -> InstrumentStates {
     InstrumentStates {
         com1_state: FrequencyState {
-            standby_freq: Frequency {
-                integer: 118,
-                fraction: 000,
-            },
-            active_freq: Frequency {
-                integer: 118,
-                fraction: 000,
-            },
+            standby_freq: Frequency::lowest(Band::Com),
+            active_freq: Frequency::lowest(Band::Com),
+            channel_spacing: ChannelSpacing::Khz25,
         },
         com2_state: FrequencyState {
-            standby_freq: Frequency {
-                integer: 118,
-                fraction: 000,
-            },
-            active_freq: Frequency {
-                integer: 118,
-                fraction: 000,
-            },
+            standby_freq: Frequency::lowest(Band::Com),
+            active_freq: Frequency::lowest(Band::Com),
+            channel_spacing: ChannelSpacing::Khz25,
         },
         nav1_state: FrequencyState {
-            standby_freq: Frequency {
-                integer: 108,
-                fraction: 000,
-            },
-            active_freq: Frequency {
-                integer: 108,
-                fraction: 000,
-            },
+            standby_freq: Frequency::lowest(Band::Nav),
+            active_freq: Frequency::lowest(Band::Nav),
+            channel_spacing: ChannelSpacing::Khz50,
         },
         nav2_state: FrequencyState {
-            standby_freq: Frequency {
-                integer: 108,
-                fraction: 000,
-            },
-            active_freq: Frequency {
-                integer: 108,
-                fraction: 000,
-            },
+            standby_freq: Frequency::lowest(Band::Nav),
+            active_freq: Frequency::lowest(Band::Nav),
+            channel_spacing: ChannelSpacing::Khz50,
         },
         adf_state: AdfState {
-            active_frequency: 123,
-            standby_frequency: 123,
+            active_frequency: Frequency::lowest(Band::Adf),
+            standby_frequency: Frequency::lowest(Band::Adf),
+            half_khz_steps: false,
         },
         dme_state: DmeState { distance: None },
         xpdr_state: XpdrState {
             code: [1, 0, 0, 0],
             selected_digit: 0,
+            mode: TransponderMode::Standby,
+            pending_code: None,
+            last_edit: Instant::now(),
+            commit_delay: XPDR_COMMIT_DELAY,
+            confirm_at: None,
+            confirm_delay: LONG_PRESS_DURATION,
+            digit_before_press: 0,
+            quick_codes: vec![[7, 0, 0, 0]],
         },
         autopilot_state: AutopilotState {
             airspeed: 0,
             heading: 0,
             altitude: 100, // Airbus A320's minimum setting is 100, might as well initiate as such
             vertical_speed: 0,
+            mach: 0,
+            flight_path_angle: 0,
             selected_setting: AutopilotValue::Altitude,
+            engaged: HashSet::new(),
+        },
+        nav1_course: CourseState {
+            degrees: 0,
+            obs_mode: false,
+            swap_at: None,
+            swap_delay: DOUBLE_PRESS_INTERVAL,
+        },
+        nav2_course: CourseState {
+            degrees: 0,
+            obs_mode: false,
+            swap_at: None,
+            swap_delay: DOUBLE_PRESS_INTERVAL,
         },
+        barometer_state: BarometerState {
+            pascal: 101_325, // standard pressure
+        },
+    }
+}
+
+/// A transponder code as written, e.g. "7000", four digits from 0 to 7
+pub fn parse_xpdr_code(code: &str) -> Result<[i8; 4], String> {
+    let digits: Vec<i8> = code
+        .chars()
+        .map(|digit| digit.to_digit(8).map(|digit| digit as i8))
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("Invalid transponder code: {:?}", code))?;
+    digits
+        .try_into()
+        .map_err(|_| format!("Transponder codes have four digits, got {:?}", code))
+}
+
+#[cfg(test)]
+mod states_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xpdr_code() {
+        assert_eq!(parse_xpdr_code("7000"), Ok([7, 0, 0, 0]));
+        assert_eq!(parse_xpdr_code("0021"), Ok([0, 0, 2, 1]));
+        assert!(parse_xpdr_code("7800").is_err());
+        assert!(parse_xpdr_code("700").is_err());
+        assert!(parse_xpdr_code("70000").is_err());
     }
 }
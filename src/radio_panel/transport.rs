@@ -0,0 +1,211 @@
+use hidapi::{HidApi, HidDevice};
+use std::{cell::RefCell, ffi::CString, rc::Rc, thread, time::Duration};
+
+use super::{
+    constants::{CONTROL_MESSAGE_SIZE, INPUT_REPORT_SIZE},
+    error::RadioPanelError,
+};
+
+const VENDOR_ID: u16 = 0x06a3; // Saitek
+const PRODUCT_ID: u16 = 0x0d05; // Radio Panel
+
+/// Raw report exchange with the Radio Panel
+/// Decouples the display and input logic from how the hardware is reached
+pub trait PanelTransport {
+    /// Wait up to timeout_ms for an input report
+    /// Returns the amount of bytes read, 0 if nothing arrived in time
+    fn read_input_report(
+        &mut self,
+        buffer: &mut [u8; INPUT_REPORT_SIZE],
+        timeout_ms: i32,
+    ) -> Result<usize, RadioPanelError>;
+
+    /// Send the contents of all displays to the hardware
+    fn write_feature_report(
+        &mut self,
+        report: &[u8; CONTROL_MESSAGE_SIZE],
+    ) -> Result<(), RadioPanelError>;
+
+    /// Try to reopen the device after it was lost
+    /// Waits up to timeout_ms, returns true once the device is usable again
+    fn reconnect(&mut self, timeout_ms: i32) -> Result<bool, RadioPanelError>;
+}
+
+/// Identifies a single Radio Panel when several are plugged in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanelInfo {
+    pub serial_number: Option<String>,
+    pub path: CString, // platform specific, can change when plugged into another port
+}
+
+impl PanelInfo {
+    /// Is this the same physical panel, even after it was plugged back in?
+    fn is_same_panel(&self, other: &PanelInfo) -> bool {
+        match (&self.serial_number, &other.serial_number) {
+            (Some(serial_number), Some(other_serial_number)) => {
+                serial_number == other_serial_number
+            }
+            _ => self.path == other.path,
+        }
+    }
+}
+
+/// Finds and opens Radio Panels
+/// hidapi can only be initialized once, so all opened panels share it
+pub struct HidPanels {
+    hid_api: Rc<RefCell<HidApi>>,
+}
+
+impl HidPanels {
+    pub fn new() -> Result<HidPanels, RadioPanelError> {
+        let hid_api = HidApi::new().map_err(RadioPanelError::Io)?;
+        Ok(HidPanels {
+            hid_api: Rc::new(RefCell::new(hid_api)),
+        })
+    }
+
+    /// All Radio Panels currently plugged in, always in the same order
+    pub fn list(&self) -> Result<Vec<PanelInfo>, RadioPanelError> {
+        let mut hid_api = self.hid_api.borrow_mut();
+        hid_api.refresh_devices().map_err(RadioPanelError::Io)?;
+        Ok(find_panels(&hid_api))
+    }
+
+    pub fn open(&self, panel: &PanelInfo) -> Result<HidTransport, RadioPanelError> {
+        let hid_device = self
+            .hid_api
+            .borrow()
+            .open_path(&panel.path)
+            .map_err(|_error| RadioPanelError::NotFound)?;
+        Ok(HidTransport {
+            hid_api: Rc::clone(&self.hid_api),
+            hid_device,
+            panel: panel.clone(),
+        })
+    }
+}
+
+fn find_panels(hid_api: &HidApi) -> Vec<PanelInfo> {
+    let mut panels: Vec<PanelInfo> = hid_api
+        .device_list()
+        .filter(|device| device.vendor_id() == VENDOR_ID && device.product_id() == PRODUCT_ID)
+        .map(|device| PanelInfo {
+            serial_number: device
+                .serial_number()
+                .filter(|serial_number| !serial_number.is_empty())
+                .map(String::from),
+            path: device.path().to_owned(),
+        })
+        .collect();
+    panels.sort_by(|a, b| a.path.cmp(&b.path));
+    panels
+}
+
+/// Talks to a physical Radio Panel over USB HID
+pub struct HidTransport {
+    hid_api: Rc<RefCell<HidApi>>, // kept around to find the panel again after it was unplugged
+    hid_device: HidDevice,
+    panel: PanelInfo,
+}
+
+impl PanelTransport for HidTransport {
+    fn read_input_report(
+        &mut self,
+        buffer: &mut [u8; INPUT_REPORT_SIZE],
+        timeout_ms: i32,
+    ) -> Result<usize, RadioPanelError> {
+        Ok(self.hid_device.read_timeout(buffer, timeout_ms)?)
+    }
+
+    fn write_feature_report(
+        &mut self,
+        report: &[u8; CONTROL_MESSAGE_SIZE],
+    ) -> Result<(), RadioPanelError> {
+        Ok(self.hid_device.send_feature_report(report)?)
+    }
+
+    fn reconnect(&mut self, timeout_ms: i32) -> Result<bool, RadioPanelError> {
+        let mut hid_api = self.hid_api.borrow_mut();
+        hid_api.refresh_devices().map_err(RadioPanelError::Io)?;
+
+        let plugged_in = find_panels(&hid_api)
+            .into_iter()
+            .find(|panel| panel.is_same_panel(&self.panel));
+
+        if let Some(panel) = plugged_in {
+            // The device can show up before it is ready to be opened, just try again next time
+            if let Ok(hid_device) = hid_api.open_path(&panel.path) {
+                self.hid_device = hid_device;
+                self.panel = panel;
+                return Ok(true);
+            }
+        }
+
+        // Other panels share hidapi, don't keep them from using it while waiting
+        drop(hid_api);
+        thread::sleep(Duration::from_millis(timeout_ms as u64));
+        Ok(false)
+    }
+}
+
+/// In-memory stand-in for the hardware
+/// Replays scripted input reports and records every feature report sent
+/// Can be unplugged and plugged back in to simulate a loose cable
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockTransport {
+    pub input_reports: std::collections::VecDeque<[u8; INPUT_REPORT_SIZE]>,
+    pub feature_reports: Vec<[u8; CONTROL_MESSAGE_SIZE]>,
+    pub unplugged: bool,
+    pub reconnect_attempts: usize,
+}
+
+#[cfg(test)]
+impl MockTransport {
+    pub fn new() -> MockTransport {
+        MockTransport::default()
+    }
+
+    /// Queue an input report, returned by the next read
+    pub fn push_input_report(&mut self, report: [u8; INPUT_REPORT_SIZE]) {
+        self.input_reports.push_back(report);
+    }
+}
+
+#[cfg(test)]
+impl PanelTransport for MockTransport {
+    fn read_input_report(
+        &mut self,
+        buffer: &mut [u8; INPUT_REPORT_SIZE],
+        _timeout_ms: i32,
+    ) -> Result<usize, RadioPanelError> {
+        if self.unplugged {
+            return Err(RadioPanelError::Disconnected);
+        }
+
+        match self.input_reports.pop_front() {
+            Some(report) => {
+                *buffer = report;
+                Ok(INPUT_REPORT_SIZE)
+            }
+            None => Ok(0), // behave like a timeout
+        }
+    }
+
+    fn write_feature_report(
+        &mut self,
+        report: &[u8; CONTROL_MESSAGE_SIZE],
+    ) -> Result<(), RadioPanelError> {
+        if self.unplugged {
+            return Err(RadioPanelError::Disconnected);
+        }
+
+        self.feature_reports.push(*report);
+        Ok(())
+    }
+
+    fn reconnect(&mut self, _timeout_ms: i32) -> Result<bool, RadioPanelError> {
+        self.reconnect_attempts += 1;
+        Ok(!self.unplugged)
+    }
+}
@@ -1,17 +1,20 @@
 /// Make sure values stay within min and max
-/// Wraps around on both ends
-pub fn wrap<T: std::cmp::PartialOrd + std::ops::Sub<Output = T> + std::ops::Add<Output = T>>(
+/// Wraps around on both ends, as often as needed
+pub fn wrap<
+    T: Copy + std::cmp::PartialOrd + std::ops::Sub<Output = T> + std::ops::Add<Output = T>,
+>(
     value: T,
     min: T,
     max: T,
 ) -> T {
-    if value < min {
-        max - (min - value)
-    } else if value >= max {
-        min + (value - max)
-    } else {
-        value
+    let mut value = value;
+    while value < min {
+        value = max - (min - value);
     }
+    while value >= max {
+        value = min + (value - max);
+    }
+    value
 }
 
 #[cfg(test)]
@@ -42,4 +45,10 @@ mod wrap_tests {
     fn test_below_min() {
         assert_eq!(wrap(90, 110, 140), 120);
     }
+
+    #[test]
+    fn test_multiple_times_around() {
+        assert_eq!(wrap(185, 110, 140), 125);
+        assert_eq!(wrap(50, 110, 140), 110);
+    }
 }
@@ -0,0 +1,1027 @@
+// End-to-end checks of the app logic without hardware or a flight simulator
+// A scenario feeds panel inputs in and checks what reaches the simulator and the windows
+
+use super::*;
+use radio_panel::constants::*;
+use simulator::{mock::MockSimulator, tracking::ChangeTracker};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// A single thing happening in a scenario
+pub enum Step {
+    /// Something done on the panel
+    Input(InputEvent),
+    /// The panel is polled without anything touched, values from the simulator are taken over
+    Idle,
+    /// The simulator now has this value
+    Variable(SimVariable, f64),
+    /// Exactly these events were sent since the previous check, in order
+    Events(Vec<SimEvent>),
+    /// The windows show this: top left, top right, bottom left, bottom right
+    Display([&'static str; 4]),
+    /// Time passes without polling the panel
+    Wait(Duration),
+    /// The simulator closes, the panel has to notice it by itself
+    SimulatorGone,
+    /// The simulator has no such variable
+    Unsupported(SimVariable),
+    /// The hardware sends this input report, e.g. a garbled one
+    Report([u8; INPUT_REPORT_SIZE]),
+}
+
+/// How long a button is held for a long press, and how quick a double press is, in scenarios
+/// Shorter than on the hardware, so the scenarios don't have to wait as long
+const SCENARIO_LONG_PRESS_DURATION: Duration = Duration::from_millis(50);
+const SCENARIO_DOUBLE_PRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs the steps against a single panel with the given assignment
+/// Knobs don't accelerate, so every detent is a single step
+pub fn run_scenario(assignment: PanelAssignment, steps: Vec<Step>) {
+    run_scenario_with(|_| (), assignment, steps)
+}
+
+/// Same as run_scenario, with settings applied to the instruments first
+/// The panel inputs go through run like on the hardware: as input reports, one per pass
+pub fn run_scenario_with(
+    setup: impl FnOnce(&mut InstrumentStates),
+    assignment: PanelAssignment,
+    steps: Vec<Step>,
+) {
+    let mut panels = [scenario_panel(assignment)];
+    let mut simulator = ChangeTracker::new(MockSimulator::new());
+    let mut state = instruments_default_state();
+    state.nav1_course.swap_delay = SCENARIO_DOUBLE_PRESS_INTERVAL;
+    state.nav2_course.swap_delay = SCENARIO_DOUBLE_PRESS_INTERVAL;
+    setup(&mut state);
+
+    let mut steps = steps.into_iter().enumerate();
+    let mut passes = VecDeque::new(); // still to come for the current input
+    let mut polled_step = None; // waiting for its passes to check the connection
+    let mut selectors = InputState::new(); // where the mode selectors are on the hardware
+    let mut last_presses = HashMap::new();
+    let mut simulator_running = true;
+
+    let between_passes = |panels: &mut [Panel<MockTransport>],
+                          simulator: &mut ChangeTracker<MockSimulator>,
+                          connected_to_sim: bool| {
+        let panel = &mut panels[0];
+        if passes.is_empty() {
+            if let Some(index) = polled_step.take() {
+                assert_eq!(
+                    connected_to_sim, simulator_running,
+                    "simulator connection at step {}",
+                    index
+                );
+            }
+            if !connected_to_sim {
+                // the mock simulator can be connected to again right away, run does so next
+                simulator_running = true;
+                return true;
+            }
+
+            for (index, step) in steps.by_ref() {
+                match step {
+                    Step::Input(event) => {
+                        passes.extend(hardware_passes(event, &mut selectors, &last_presses));
+                        polled_step = Some(index);
+                        break;
+                    }
+                    Step::Idle => {
+                        passes.push_back((Duration::ZERO, None));
+                        polled_step = Some(index);
+                        break;
+                    }
+                    Step::Report(report) => {
+                        panel.radio_panel.transport_mut().push_input_report(report);
+                        passes.push_back((Duration::ZERO, None));
+                        polled_step = Some(index);
+                        break;
+                    }
+                    Step::Variable(variable, value) => {
+                        simulator.backend.variables.insert(variable, value);
+                    }
+                    Step::Events(expected) => {
+                        assert_eq!(
+                            simulator.backend.take_events(),
+                            expected,
+                            "events at step {}",
+                            index
+                        )
+                    }
+                    Step::Wait(duration) => std::thread::sleep(duration),
+                    Step::SimulatorGone => {
+                        simulator.backend.connected = false;
+                        simulator_running = false;
+                    }
+                    Step::Unsupported(variable) => simulator.backend.unsupported.push(variable),
+                    Step::Display(expected) => {
+                        let shown = [
+                            Window::TopLeft,
+                            Window::TopRight,
+                            Window::BottomLeft,
+                            Window::BottomRight,
+                        ]
+                        .map(|window| panel.radio_panel.window_text(window));
+                        assert_eq!(
+                            shown,
+                            expected.map(String::from),
+                            "display at step {}",
+                            index
+                        );
+                    }
+                }
+            }
+        }
+
+        match passes.pop_front() {
+            Some((wait, report)) => {
+                std::thread::sleep(wait);
+                if let Some(input) = report {
+                    push_report(&mut panel.radio_panel, input, &mut last_presses);
+                }
+                true
+            }
+            None => false, // all steps done
+        }
+    };
+    run(
+        &mut panels,
+        &mut state,
+        &mut simulator,
+        Duration::ZERO, // every idle pass takes over the simulator's values
+        Duration::ZERO, // reconnects in the pass right after the simulator is back
+        between_passes,
+    )
+    .unwrap();
+}
+
+/// A panel on mock hardware
+fn scenario_panel(assignment: PanelAssignment) -> Panel<MockTransport> {
+    Panel {
+        radio_panel: RadioPanel::with_transport(MockTransport::new()),
+        input: InputState::new(),
+        accelerator: RotaryAccelerator::new(AccelerationCurve::linear()),
+        gestures: GestureTracker::new(SCENARIO_LONG_PRESS_DURATION, SCENARIO_DOUBLE_PRESS_INTERVAL),
+        assignment,
+        pages: PageRegistry::default(),
+    }
+}
+
+/// What the hardware reports for an input, each to be read in a pass of its own after a wait
+/// None is a pass without a report, like a timeout
+/// A press is followed by a release, a long press waits for it in between
+/// Presses that aren't meant as a double press wait for the double press interval to pass
+fn hardware_passes(
+    event: InputEvent,
+    selectors: &mut InputState,
+    last_presses: &HashMap<Button, Instant>,
+) -> Vec<(Duration, Option<InputState>)> {
+    let release = Some(selectors.idle());
+    let press = |button| Some(selectors.with_event(InputEvent::ButtonPressed(button)));
+    let until_single = |button| {
+        last_presses
+            .get(&button)
+            .map_or(Duration::ZERO, |pressed: &Instant| {
+                // a little more, the panel is polled a moment after the report
+                (SCENARIO_DOUBLE_PRESS_INTERVAL + Duration::from_millis(10))
+                    .saturating_sub(pressed.elapsed())
+            })
+    };
+    match event {
+        InputEvent::ButtonPressed(button) => {
+            vec![
+                (until_single(button), press(button)),
+                (Duration::ZERO, release),
+            ]
+        }
+        InputEvent::ButtonDoublePressed(button) => vec![
+            (until_single(button), press(button)),
+            (Duration::ZERO, release),
+            (Duration::ZERO, press(button)),
+            (Duration::ZERO, release),
+        ],
+        InputEvent::ButtonHeld(button) => vec![
+            (until_single(button), press(button)),
+            (SCENARIO_LONG_PRESS_DURATION, None),
+            (Duration::ZERO, release),
+        ],
+        InputEvent::ButtonReleased(_) => vec![(Duration::ZERO, release)],
+        InputEvent::RotaryTurned { .. } => {
+            vec![(Duration::ZERO, Some(selectors.with_event(event)))]
+        }
+        InputEvent::ModeChanged { .. } => {
+            *selectors = selectors.with_event(event);
+            vec![(Duration::ZERO, Some(*selectors))]
+        }
+    }
+}
+
+/// Queues the input report for the hardware state, read by the next pass
+fn push_report(
+    radio_panel: &mut RadioPanel<MockTransport>,
+    input: InputState,
+    last_presses: &mut HashMap<Button, Instant>,
+) {
+    for (button, state) in [
+        (Button::Upper, input.button_upper),
+        (Button::Lower, input.button_lower),
+    ] {
+        if matches!(state, ButtonState::Pressed) {
+            last_presses.insert(button, Instant::now());
+        }
+    }
+    radio_panel
+        .transport_mut()
+        .push_input_report(input_report(&input));
+}
+
+/// The three bytes the hardware sends for an input state
+fn input_report(input: &InputState) -> [u8; INPUT_REPORT_SIZE] {
+    let selector = |mode, bitmasks: [u32; 7]| {
+        bitmasks[match mode {
+            ModeSelectorState::ModeSelectorCom1 => 0,
+            ModeSelectorState::ModeSelectorCom2 => 1,
+            ModeSelectorState::ModeSelectorNav1 => 2,
+            ModeSelectorState::ModeSelectorNav2 => 3,
+            ModeSelectorState::ModeSelectorAdf => 4,
+            ModeSelectorState::ModeSelectorDme => 5,
+            ModeSelectorState::ModeSelectorXpdr => 6,
+        }]
+    };
+    let mut report = selector(
+        input.mode_selector_upper,
+        [
+            BITMASK_MODE_SELECTOR_UPPER_COM1,
+            BITMASK_MODE_SELECTOR_UPPER_COM2,
+            BITMASK_MODE_SELECTOR_UPPER_NAV1,
+            BITMASK_MODE_SELECTOR_UPPER_NAV2,
+            BITMASK_MODE_SELECTOR_UPPER_ADF,
+            BITMASK_MODE_SELECTOR_UPPER_DME,
+            BITMASK_MODE_SELECTOR_UPPER_XPDR,
+        ],
+    ) | selector(
+        input.mode_selector_lower,
+        [
+            BITMASK_MODE_SELECTOR_LOWER_COM1,
+            BITMASK_MODE_SELECTOR_LOWER_COM2,
+            BITMASK_MODE_SELECTOR_LOWER_NAV1,
+            BITMASK_MODE_SELECTOR_LOWER_NAV2,
+            BITMASK_MODE_SELECTOR_LOWER_ADF,
+            BITMASK_MODE_SELECTOR_LOWER_DME,
+            BITMASK_MODE_SELECTOR_LOWER_XPDR,
+        ],
+    );
+    for (button, bitmask) in [
+        (input.button_upper, BITMASK_BUTTON_UPPER_PRESSED),
+        (input.button_lower, BITMASK_BUTTON_LOWER_PRESSED),
+    ] {
+        if matches!(button, ButtonState::Pressed) {
+            report |= bitmask;
+        }
+    }
+    for (rotary, clockwise, counterclockwise) in [
+        (
+            input.rotary_upper_inner,
+            BITMASK_ROTARY_UPPER_INNER_CLOCKWISE,
+            BITMASK_ROTARY_UPPER_INNER_COUNTERCLOCKWISE,
+        ),
+        (
+            input.rotary_upper_outer,
+            BITMASK_ROTARY_UPPER_OUTER_CLOCKWISE,
+            BITMASK_ROTARY_UPPER_OUTER_COUNTERCLOCKWISE,
+        ),
+        (
+            input.rotary_lower_inner,
+            BITMASK_ROTARY_LOWER_INNER_CLOCKWISE,
+            BITMASK_ROTARY_LOWER_INNER_COUNTERCLOCKWISE,
+        ),
+        (
+            input.rotary_lower_outer,
+            BITMASK_ROTARY_LOWER_OUTER_CLOCKWISE,
+            BITMASK_ROTARY_LOWER_OUTER_COUNTERCLOCKWISE,
+        ),
+    ] {
+        report |= match rotary {
+            RotaryState::Clockwise => clockwise,
+            RotaryState::CounterClockwise => counterclockwise,
+            RotaryState::None => 0,
+        };
+    }
+    [(report >> 16) as u8, (report >> 8) as u8, report as u8]
+}
+
+fn turn(knob: Knob, direction: Direction) -> Step {
+    Step::Input(InputEvent::RotaryTurned { knob, direction })
+}
+
+fn press(button: Button) -> Step {
+    Step::Input(InputEvent::ButtonPressed(button))
+}
+
+fn double_press(button: Button) -> Step {
+    Step::Input(InputEvent::ButtonDoublePressed(button))
+}
+
+fn hold(button: Button) -> Step {
+    Step::Input(InputEvent::ButtonHeld(button))
+}
+
+fn select(selector: Selector, from: ModeSelectorState, to: ModeSelectorState) -> Step {
+    Step::Input(InputEvent::ModeChanged { selector, from, to })
+}
+
+#[test]
+fn test_standby_screen_until_touched() {
+    run_scenario(
+        PanelAssignment::all(),
+        vec![Step::Display(["-----", "-----", "-----", "-----"])],
+    );
+}
+
+#[test]
+fn test_tune_and_swap_com1() {
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Idle,
+            // neither DME on the lower half nor the untouched COM1 send anything
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorDme,
+            ),
+            Step::Events(Vec::new()),
+            turn(Knob::UpperOuter, Direction::Clockwise),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Events(vec![
+                SimEvent::StandbyFrequency {
+                    radio: Radio::Com1,
+                    frequency_hz: 119_000_000,
+                },
+                SimEvent::StandbyFrequency {
+                    radio: Radio::Com1,
+                    frequency_hz: 119_025_000,
+                },
+            ]),
+            Step::Display(["18.000", "19.025", "108.00", "    -"]),
+            press(Button::Upper),
+            Step::Events(vec![SimEvent::SwapFrequencies(Radio::Com1)]),
+            Step::Display(["19.025", "18.000", "108.00", "    -"]),
+            // the simulator swapped as well, nothing to correct
+            Step::Idle,
+            Step::Events(Vec::new()),
+            Step::Display(["19.025", "18.000", "108.00", "    -"]),
+        ],
+    );
+}
+
+#[test]
+fn test_xpdr_on_lower_half() {
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorXpdr,
+            ),
+            turn(Knob::LowerInner, Direction::CounterClockwise),
+            press(Button::Lower),
+            turn(Knob::LowerInner, Direction::CounterClockwise),
+            Step::Display(["18.000", "18.000", "    1", " 0700"]),
+        ],
+    );
+}
+
+#[test]
+fn test_xpdr_mode_and_ident() {
+    run_scenario(
+        "com1,xpdr".parse().unwrap(),
+        vec![
+            Step::Variable(
+                SimVariable::TransponderMode,
+                f64::from(TransponderMode::On as u8),
+            ),
+            Step::Idle,
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorXpdr,
+            ),
+            Step::Display(["18.000", "18.000", "    2", " 1000"]),
+            // ALT is the last position
+            turn(Knob::LowerOuter, Direction::Clockwise),
+            turn(Knob::LowerOuter, Direction::Clockwise),
+            Step::Display(["18.000", "18.000", "    3", " 1000"]),
+            hold(Button::Lower),
+            Step::Events(vec![
+                SimEvent::TransponderMode(TransponderMode::Altitude),
+                SimEvent::TransponderIdent,
+            ]),
+            // IDENT in the middle of dialing, on the last digit
+            press(Button::Lower),
+            press(Button::Lower),
+            press(Button::Lower),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            hold(Button::Lower),
+            Step::Events(vec![SimEvent::TransponderIdent]),
+            // still on the last digit, nothing squawked
+            turn(Knob::LowerInner, Direction::Clockwise),
+            Step::Display(["18.000", "18.000", "    3", " 1002"]),
+            Step::Events(Vec::new()),
+        ],
+    );
+}
+
+#[test]
+fn test_xpdr_codes_are_squawked_once_dialed() {
+    run_scenario_with(
+        |state| {
+            state.xpdr_state.commit_delay = Duration::from_millis(100);
+            state.xpdr_state.confirm_delay = Duration::from_millis(50);
+        },
+        "com1,xpdr".parse().unwrap(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::TransponderCode, 7000.0),
+            Step::Variable(SimVariable::TransponderMode, 4.0),
+            Step::Idle,
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorXpdr,
+            ),
+            // through 0000, 1000 and 1100 without squawking any of them
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            press(Button::Lower),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            Step::Display(["18.000", "18.000", "    3", " 1200"]),
+            Step::Events(Vec::new()),
+            // past the last digit confirms, unless it was the start of a double press
+            press(Button::Lower),
+            press(Button::Lower),
+            press(Button::Lower),
+            Step::Events(Vec::new()),
+            Step::Wait(Duration::from_millis(60)),
+            Step::Idle,
+            Step::Events(vec![SimEvent::TransponderCode(1200)]),
+            // left alone, the code is taken over as well
+            turn(Knob::LowerInner, Direction::Clockwise),
+            Step::Events(Vec::new()),
+            Step::Wait(Duration::from_millis(150)),
+            Step::Idle,
+            Step::Events(vec![SimEvent::TransponderCode(2200)]),
+            // a double press recalls the VFR code
+            double_press(Button::Lower),
+            Step::Display(["18.000", "18.000", "    3", " 7000"]),
+            Step::Events(vec![SimEvent::TransponderCode(7000)]),
+        ],
+    );
+}
+
+#[test]
+fn test_xpdr_double_press_on_the_last_digit() {
+    run_scenario(
+        "com1,xpdr".parse().unwrap(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::TransponderCode, 2000.0),
+            Step::Variable(SimVariable::TransponderMode, 4.0),
+            Step::Idle,
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorXpdr,
+            ),
+            // 7700 dialed up to the last digit
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            press(Button::Lower),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            press(Button::Lower),
+            press(Button::Lower),
+            Step::Display(["18.000", "18.000", "    3", " 7700"]),
+            // the first press of the double press goes past the last digit
+            double_press(Button::Lower),
+            Step::Display(["18.000", "18.000", "    3", " 7000"]),
+            Step::Events(vec![SimEvent::TransponderCode(7000)]),
+        ],
+    );
+}
+
+#[test]
+fn test_dme_from_simulator() {
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            select(
+                Selector::Upper,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorDme,
+            ),
+            Step::Display(["108.00", "    -", "18.000", "18.000"]),
+            Step::Variable(SimVariable::DmeDistance, 12.34),
+            Step::Idle,
+            Step::Display(["108.00", "  12.3", "18.000", "18.000"]),
+        ],
+    );
+}
+
+#[test]
+fn test_frequencies_follow_the_simulator() {
+    run_scenario(
+        // both selectors start on COM1, keep out of the autopilot page
+        "com1,com2,nav1,nav2,adf,dme,xpdr".parse().unwrap(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 121_500_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 124_850_000.0),
+            Step::Variable(SimVariable::TransponderCode, 7000.0),
+            Step::Variable(SimVariable::TransponderMode, 1.0),
+            Step::Idle,
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorXpdr,
+            ),
+            Step::Display(["21.500", "24.850", "    1", " 7000"]),
+            // tuning continues from the frequency the simulator had
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Display(["21.500", "24.875", "    1", " 7000"]),
+            // only what the panel changed goes back
+            Step::Events(vec![SimEvent::StandbyFrequency {
+                radio: Radio::Com1,
+                frequency_hz: 124_875_000,
+            }]),
+        ],
+    );
+}
+
+#[test]
+fn test_values_arriving_after_connecting() {
+    let com1_standby = |frequency_hz| SimEvent::StandbyFrequency {
+        radio: Radio::Com1,
+        frequency_hz,
+    };
+
+    run_scenario(
+        "com1,com2,nav1,nav2,adf,dme,xpdr".parse().unwrap(),
+        vec![
+            // like FS2020, nothing to read right after connecting
+            Step::Idle,
+            Step::Display(["18.000", "18.000", "18.000", "18.000"]),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Display(["18.000", "18.025", "18.000", "18.025"]),
+            Step::Events(Vec::new()),
+            // the aircraft's radios, e.g. from the flight plan
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 121_500_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 124_850_000.0),
+            Step::Idle,
+            Step::Display(["21.500", "24.850", "21.500", "24.850"]),
+            Step::Events(Vec::new()),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Events(vec![com1_standby(124_875_000)]),
+        ],
+    );
+}
+
+#[test]
+fn test_8_33_khz_channels() {
+    let com1_standby = |frequency_hz| SimEvent::StandbyFrequency {
+        radio: Radio::Com1,
+        frequency_hz,
+    };
+
+    run_scenario_with(
+        |state| state.com1_state.channel_spacing = ChannelSpacing::Khz8_33,
+        "com1,dme".parse().unwrap(),
+        vec![
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorDme,
+            ),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 132_825_000.0),
+            Step::Idle,
+            // Named after the 25 kHz channel on the same frequency
+            Step::Display(["18.000", "32.825", "108.00", "    -"]),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Display(["18.000", "32.835", "108.00", "    -"]),
+            // 132.830 is the same frequency as 132.825
+            Step::Events(vec![com1_standby(132_833_333)]),
+        ],
+    );
+}
+
+#[test]
+fn test_tune_adf() {
+    let adf_standby = |frequency_hz| SimEvent::StandbyFrequency {
+        radio: Radio::Adf,
+        frequency_hz,
+    };
+
+    run_scenario(
+        "com1,com2,nav1,nav2,adf,dme,xpdr".parse().unwrap(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Adf), 345_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Adf), 1_750_000.0),
+            Step::Idle,
+            select(
+                Selector::Upper,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorAdf,
+            ),
+            Step::Display(["  345", " 1750", "18.000", "18.000"]),
+            // past 1799 kHz starts over at 190 kHz
+            turn(Knob::UpperOuter, Direction::Clockwise),
+            turn(Knob::UpperInner, Direction::CounterClockwise),
+            Step::Display(["  345", "  239", "18.000", "18.000"]),
+            press(Button::Upper),
+            Step::Display(["  239", "  345", "18.000", "18.000"]),
+            Step::Events(vec![
+                adf_standby(240_000),
+                adf_standby(239_000),
+                SimEvent::SwapFrequencies(Radio::Adf),
+            ]),
+        ],
+    );
+}
+
+#[test]
+fn test_autopilot_modes() {
+    let value = |target| SimVariable::AutopilotValue(target);
+    let engaged = |mode| SimVariable::AutopilotEngaged(mode);
+    run_scenario(
+        "ap".parse().unwrap(),
+        vec![
+            Step::Variable(value(AutopilotTarget::Airspeed), 120.0),
+            Step::Variable(value(AutopilotTarget::Heading), 90.0),
+            Step::Variable(value(AutopilotTarget::Altitude), 5000.0),
+            Step::Variable(value(AutopilotTarget::VerticalSpeed), -500.0),
+            Step::Variable(engaged(AutopilotMode::Master), 1.0),
+            Step::Variable(engaged(AutopilotMode::Heading), 1.0),
+            Step::Idle,
+            Step::Display([" . 120", "  090.", "05000", "-0500"]),
+            Step::Events(Vec::new()),
+            // the selected altitude is held
+            double_press(Button::Lower),
+            Step::Events(vec![SimEvent::ToggleAutopilot(AutopilotMode::Altitude)]),
+            Step::Display([" . 120", "  090.", "05000.", "-0500"]),
+            // speed on a long press of the upper button
+            hold(Button::Upper),
+            Step::Events(vec![SimEvent::ToggleAutopilot(AutopilotMode::Airspeed)]),
+            // the master on a long press of the lower one, still on altitude afterwards
+            hold(Button::Lower),
+            Step::Events(vec![SimEvent::ToggleAutopilot(AutopilotMode::Master)]),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            Step::Events(vec![SimEvent::AutopilotValue {
+                target: AutopilotTarget::Altitude,
+                value: 5100,
+            }]),
+            Step::Idle,
+            Step::Display(["  120.", "  090.", "05100.", "-0500"]),
+            // the simulator has the final say
+            Step::Variable(engaged(AutopilotMode::Heading), 0.0),
+            Step::Idle,
+            Step::Display(["  120.", "  090", "05100.", "-0500"]),
+        ],
+    );
+}
+
+#[test]
+fn test_nav_course() {
+    run_scenario(
+        "com1,nav1".parse().unwrap(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Nav1), 110_500_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Nav1), 108_000_000.0),
+            Step::Variable(SimVariable::Course(Radio::Nav1), 45.0),
+            Step::Idle,
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorNav1,
+            ),
+            Step::Display(["18.000", "18.000", "110.50", "108.00"]),
+            // the knobs move on to the course, the frequencies stay as they were
+            double_press(Button::Lower),
+            Step::Display(["18.000", "18.000", "110.50", "  045"]),
+            turn(Knob::LowerOuter, Direction::CounterClockwise),
+            turn(Knob::LowerInner, Direction::Clockwise),
+            Step::Display(["18.000", "18.000", "110.50", "  036"]),
+            Step::Events(vec![
+                SimEvent::Course {
+                    radio: Radio::Nav1,
+                    degrees: 35,
+                },
+                SimEvent::Course {
+                    radio: Radio::Nav1,
+                    degrees: 36,
+                },
+            ]),
+            // turned in the cockpit
+            Step::Variable(SimVariable::Course(Radio::Nav1), 90.0),
+            Step::Idle,
+            Step::Display(["18.000", "18.000", "110.50", "  090"]),
+            double_press(Button::Lower),
+            Step::Display(["18.000", "18.000", "110.50", "108.00"]),
+            // neither press of a double press swaps
+            Step::Wait(SCENARIO_DOUBLE_PRESS_INTERVAL),
+            Step::Idle,
+            Step::Events(Vec::new()),
+            // a single press does, once it can't be the start of a double press anymore
+            press(Button::Lower),
+            Step::Events(Vec::new()),
+            Step::Wait(SCENARIO_DOUBLE_PRESS_INTERVAL),
+            Step::Idle,
+            Step::Events(vec![SimEvent::SwapFrequencies(Radio::Nav1)]),
+            Step::Display(["18.000", "18.000", "108.00", "110.50"]),
+        ],
+    );
+}
+
+#[test]
+fn test_autopilot_altitude_fits_the_window() {
+    let altitude = SimVariable::AutopilotValue(AutopilotTarget::Altitude);
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            Step::Variable(altitude, 99_500.0),
+            Step::Idle,
+            turn(Knob::LowerOuter, Direction::Clockwise),
+            Step::Display(["    0", "  000", "99999", " 0000"]),
+            // reported by the simulator
+            Step::Variable(altitude, 120_000.0),
+            Step::Idle,
+            Step::Display(["    0", "  000", "99999", " 0000"]),
+        ],
+    );
+}
+
+#[test]
+fn test_unsupported_values_are_hidden() {
+    let to_xpdr = |selector| {
+        select(
+            selector,
+            ModeSelectorState::ModeSelectorCom1,
+            ModeSelectorState::ModeSelectorXpdr,
+        )
+    };
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            Step::Variable(SimVariable::AutopilotValue(AutopilotTarget::Mach), 78.0),
+            Step::Variable(
+                SimVariable::AutopilotValue(AutopilotTarget::FlightPathAngle),
+                -30.0,
+            ),
+            Step::Idle,
+            to_xpdr(Selector::Upper),
+            to_xpdr(Selector::Lower),
+            Step::Display(["     ", "  0.78", "     ", "  -3.0"]),
+            Step::Unsupported(SimVariable::AutopilotValue(
+                AutopilotTarget::FlightPathAngle,
+            )),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Display(["     ", "  0.79", "     ", "     "]),
+            Step::Unsupported(SimVariable::AutopilotValue(AutopilotTarget::Mach)),
+            Step::Idle,
+            Step::Display(["     ", "     ", "     ", "     "]),
+        ],
+    );
+}
+
+#[test]
+fn test_mach_page_sends_only_what_is_turned() {
+    let angle = SimVariable::AutopilotValue(AutopilotTarget::FlightPathAngle);
+    let to_xpdr = |selector| {
+        select(
+            selector,
+            ModeSelectorState::ModeSelectorCom1,
+            ModeSelectorState::ModeSelectorXpdr,
+        )
+    };
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            Step::Variable(SimVariable::AutopilotValue(AutopilotTarget::Mach), 78.0),
+            Step::Variable(angle, -30.0),
+            Step::Idle,
+            to_xpdr(Selector::Upper),
+            to_xpdr(Selector::Lower),
+            Step::Events(Vec::new()),
+            // set in the cockpit, the panel hasn't synced it yet
+            Step::Variable(angle, -20.0),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Events(vec![SimEvent::AutopilotValue {
+                target: AutopilotTarget::Mach,
+                value: 79,
+            }]),
+        ],
+    );
+}
+
+#[test]
+fn test_barometer_not_sent_before_reported() {
+    let to_adf = |selector| {
+        select(
+            selector,
+            ModeSelectorState::ModeSelectorCom1,
+            ModeSelectorState::ModeSelectorAdf,
+        )
+    };
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            to_adf(Selector::Upper),
+            // showing a radio doesn't set it
+            Step::Events(Vec::new()),
+            to_adf(Selector::Lower),
+            Step::Idle,
+            Step::Display(["     ", " 1013", "     ", " 29.92"]),
+            // the simulator has no setting to read, so the standard pressure stays on the panel
+            Step::Events(Vec::new()),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Display(["     ", " 1014", "     ", " 29.94"]),
+            // nor after turning, the simulator could have any setting
+            Step::Events(Vec::new()),
+            Step::Variable(SimVariable::Barometer, 100_000.0),
+            Step::Idle,
+            Step::Display(["     ", " 1000", "     ", " 29.53"]),
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Events(vec![SimEvent::Barometer(100_100)]),
+        ],
+    );
+}
+
+#[test]
+fn test_pages() {
+    run_scenario(
+        PanelAssignment::all(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Nav1), 110_500_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Nav1), 108_000_000.0),
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Adf), 190_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Adf), 190_000.0),
+            Step::Variable(SimVariable::Course(Radio::Nav1), 45.0),
+            Step::Variable(SimVariable::Course(Radio::Nav2), 270.0),
+            Step::Variable(SimVariable::Barometer, 101_325.0),
+            Step::Idle,
+            // the autopilot page is the one for COM1
+            Step::Display(["    0", "  000", "00100", " 0000"]),
+            // nothing touched, the panel's defaults stay out of the simulator
+            Step::Events(Vec::new()),
+            select(
+                Selector::Upper,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorNav1,
+            ),
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorCom1,
+                ModeSelectorState::ModeSelectorNav1,
+            ),
+            Step::Display(["110.50", "  045", "108.00", "  270"]),
+            turn(Knob::UpperOuter, Direction::Clockwise),
+            Step::Events(vec![SimEvent::Course {
+                radio: Radio::Nav1,
+                degrees: 55,
+            }]),
+            select(
+                Selector::Upper,
+                ModeSelectorState::ModeSelectorNav1,
+                ModeSelectorState::ModeSelectorAdf,
+            ),
+            select(
+                Selector::Lower,
+                ModeSelectorState::ModeSelectorNav1,
+                ModeSelectorState::ModeSelectorAdf,
+            ),
+            Step::Display(["     ", " 1013", "     ", " 29.92"]),
+            turn(Knob::LowerInner, Direction::CounterClockwise),
+            Step::Display(["     ", " 1013", "     ", " 29.91"]),
+            Step::Events(vec![SimEvent::Barometer(101_287)]),
+        ],
+    );
+}
+
+#[test]
+fn test_simulator_gone_while_idle() {
+    run_scenario(
+        "com1".parse().unwrap(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Idle,
+            // nothing changes on the panel, so nothing is sent that could fail
+            Step::SimulatorGone,
+            Step::Idle,
+            Step::Events(Vec::new()),
+            // connected again in the next pass, starting over with the simulator's values
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Events(vec![SimEvent::StandbyFrequency {
+                radio: Radio::Com1,
+                frequency_hz: 118_025_000,
+            }]),
+        ],
+    );
+
+    // the autopilot page as well
+    run_scenario(
+        PanelAssignment::all(),
+        vec![Step::Idle, Step::SimulatorGone, Step::Idle],
+    );
+}
+
+#[test]
+fn test_connecting_animation_stops_when_touched() {
+    let mut panels = [scenario_panel(PanelAssignment::all())];
+    let mut simulator = ChangeTracker::new(MockSimulator {
+        offline: true,
+        ..MockSimulator::new()
+    });
+    let mut passes = 0;
+    run(
+        &mut panels,
+        &mut instruments_default_state(),
+        &mut simulator,
+        Duration::ZERO,
+        Duration::from_secs(3600), // a single attempt while the test runs
+        |panels, simulator, connected_to_sim| {
+            assert!(!connected_to_sim);
+            let radio_panel = &mut panels[0].radio_panel;
+            passes += 1;
+            match passes {
+                1 => {
+                    assert!(radio_panel.is_animating());
+                    let touched =
+                        InputState::new().with_event(InputEvent::ButtonPressed(Button::Upper));
+                    radio_panel
+                        .transport_mut()
+                        .push_input_report(input_report(&touched));
+                }
+                // stopped, and not started again by the next passes
+                _ => assert!(!radio_panel.is_animating()),
+            }
+            assert_eq!(simulator.backend.connect_attempts, 1);
+            passes < 3
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_malformed_report_is_skipped() {
+    run_scenario(
+        "com1".parse().unwrap(),
+        vec![
+            Step::Variable(SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            Step::Variable(SimVariable::StandbyFrequency(Radio::Com1), 118_000_000.0),
+            Step::Idle,
+            // a turned knob, but no mode selector in any position
+            Step::Report([0b0000_0000, 0b0000_0000, 0b0000_0001]),
+            Step::Events(Vec::new()),
+            // still polling
+            turn(Knob::UpperInner, Direction::Clockwise),
+            Step::Events(vec![SimEvent::StandbyFrequency {
+                radio: Radio::Com1,
+                frequency_hz: 118_025_000,
+            }]),
+        ],
+    );
+}
+
+#[test]
+fn test_unassigned_modes_stay_dark() {
+    run_scenario(
+        "xpdr".parse().unwrap(),
+        vec![
+            Step::Idle,
+            Step::Events(Vec::new()),
+            Step::Display(["     ", "     ", "     ", "     "]),
+        ],
+    );
+}
@@ -0,0 +1,173 @@
+/// Radios with an active and a standby frequency
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Radio {
+    Com1,
+    Com2,
+    Nav1,
+    Nav2,
+    Adf,
+}
+
+/// Autopilot values the panel can set
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AutopilotTarget {
+    Heading,         // degrees
+    Altitude,        // feet
+    VerticalSpeed,   // feet per minute
+    Airspeed,        // knots
+    Mach,            // hundredths, e.g. 78 for Mach 0.78
+    FlightPathAngle, // tenths of a degree, negative when descending
+}
+
+/// Autopilot modes the panel can engage and disengage
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AutopilotMode {
+    Master,
+    Heading,       // hold the heading bug
+    Altitude,      // hold the selected altitude
+    VerticalSpeed, // climb or descend at the selected rate
+    Airspeed,      // hold the selected speed
+}
+
+impl AutopilotMode {
+    pub const ALL: [AutopilotMode; 5] = [
+        AutopilotMode::Master,
+        AutopilotMode::Heading,
+        AutopilotMode::Altitude,
+        AutopilotMode::VerticalSpeed,
+        AutopilotMode::Airspeed,
+    ];
+}
+
+/// Positions of the transponder's mode switch
+/// Numbered the way MSFS's TRANSPONDER STATE does, which skips TEST
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TransponderMode {
+    Off = 0,
+    Standby = 1,
+    On = 3,       // mode A, code only
+    Altitude = 4, // mode C, code and pressure altitude
+}
+
+impl TransponderMode {
+    /// In the order of the switch, from OFF to ALT
+    pub const ALL: [TransponderMode; 4] = [
+        TransponderMode::Off,
+        TransponderMode::Standby,
+        TransponderMode::On,
+        TransponderMode::Altitude,
+    ];
+
+    /// The mode a SimVariable::TransponderMode value stands for
+    /// None for positions the panel doesn't offer, e.g. TEST or GND
+    pub fn from_value(value: f64) -> Option<TransponderMode> {
+        TransponderMode::ALL
+            .into_iter()
+            .find(|mode| f64::from(*mode as u8) == value.round())
+    }
+}
+
+/// A value the panel wants to set in the simulator
+/// Independent of how a specific simulator expects it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SimEvent {
+    ActiveFrequency { radio: Radio, frequency_hz: u32 },
+    StandbyFrequency { radio: Radio, frequency_hz: u32 },
+    TransponderCode(u16), // as written, e.g. 7000
+    TransponderMode(TransponderMode),
+    TransponderIdent, // press IDENT once
+    AutopilotValue { target: AutopilotTarget, value: i32 },
+    Course { radio: Radio, degrees: u16 }, // of a NAV radio, selected with the OBS knob
+    Barometer(u32),                        // altimeter setting in pascal, e.g. 101325
+    ToggleAutopilot(AutopilotMode), // engage if off, disengage if on, like the button in the cockpit
+    SwapFrequencies(Radio), // exchange active and standby the way the aircraft's own button does
+}
+
+impl SimEvent {
+    /// Which variable the event sets and to what value, None if it doesn't set a single one
+    pub fn variable_value(&self) -> Option<(SimVariable, f64)> {
+        let variable_value = match *self {
+            SimEvent::ActiveFrequency {
+                radio,
+                frequency_hz,
+            } => (SimVariable::ActiveFrequency(radio), f64::from(frequency_hz)),
+            SimEvent::StandbyFrequency {
+                radio,
+                frequency_hz,
+            } => (
+                SimVariable::StandbyFrequency(radio),
+                f64::from(frequency_hz),
+            ),
+            SimEvent::TransponderCode(code) => (SimVariable::TransponderCode, f64::from(code)),
+            SimEvent::TransponderMode(mode) => {
+                (SimVariable::TransponderMode, f64::from(mode as u8))
+            }
+            SimEvent::AutopilotValue { target, value } => {
+                (SimVariable::AutopilotValue(target), f64::from(value))
+            }
+            SimEvent::Course { radio, degrees } => (SimVariable::Course(radio), f64::from(degrees)),
+            SimEvent::Barometer(pascal) => (SimVariable::Barometer, f64::from(pascal)),
+            SimEvent::SwapFrequencies(_)
+            | SimEvent::TransponderIdent
+            | SimEvent::ToggleAutopilot(_) => return None,
+        };
+        Some(variable_value)
+    }
+}
+
+/// Values the panel reads from the simulator
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SimVariable {
+    ActiveFrequency(Radio),          // Hz
+    StandbyFrequency(Radio),         // Hz
+    TransponderCode,                 // as written, e.g. 7000
+    TransponderMode,                 // as TransponderMode numbers it
+    AutopilotValue(AutopilotTarget), // same unit as when setting it
+    AutopilotEngaged(AutopilotMode), // not 0 while engaged
+    Course(Radio),                   // degrees
+    Barometer,                       // pascal
+    DmeDistance,                     // nautical miles
+}
+
+/// A flight simulator the panel can control
+pub trait SimulatorBackend {
+    /// Try to connect once, returns true when connected
+    fn connect(&mut self) -> bool;
+
+    /// Returns false if the simulator can't be reached anymore
+    /// Values the simulator has no equivalent for are left out
+    fn send(&mut self, event: SimEvent) -> bool;
+
+    /// None if the simulator doesn't have a valid value right now
+    fn read_variable(&mut self, variable: SimVariable) -> Option<f64>;
+
+    /// Returns false if the simulator can't be reached anymore, without sending anything
+    fn is_connected(&mut self) -> bool;
+
+    /// Whether the simulator has the variable at all, the panel doesn't show the ones it hasn't
+    fn supports(&self, _variable: SimVariable) -> bool {
+        true
+    }
+}
+
+impl<T: SimulatorBackend + ?Sized> SimulatorBackend for Box<T> {
+    fn connect(&mut self) -> bool {
+        (**self).connect()
+    }
+
+    fn send(&mut self, event: SimEvent) -> bool {
+        (**self).send(event)
+    }
+
+    fn read_variable(&mut self, variable: SimVariable) -> Option<f64> {
+        (**self).read_variable(variable)
+    }
+
+    fn is_connected(&mut self) -> bool {
+        (**self).is_connected()
+    }
+
+    fn supports(&self, variable: SimVariable) -> bool {
+        (**self).supports(variable)
+    }
+}
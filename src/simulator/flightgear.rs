@@ -0,0 +1,601 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{SocketAddr, TcpStream},
+    time::{Duration, Instant},
+};
+
+use super::backend::*;
+
+/// Port of FlightGear's telnet property server when started with --telnet=5401
+pub const FLIGHTGEAR_DEFAULT_PORT: u16 = 5401;
+
+/// How long to wait for FlightGear to accept the connection or answer
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the values read in one go are used before reading them all again
+/// A sync reads every variable, this way it takes a single round trip
+const VALUES_MAX_AGE: Duration = Duration::from_millis(200);
+
+/// How long IDENT stays pressed, FlightGear keeps identing as long as it is
+const IDENT_PRESS_DURATION: Duration = Duration::from_millis(500);
+
+/// Every property the panel reads and sets
+/// Value in FlightGear times the factor is the value in the unit of the SimVariable
+const PROPERTIES: [(SimVariable, &str, f64); 20] = [
+    (
+        SimVariable::ActiveFrequency(Radio::Com1),
+        "/instrumentation/comm[0]/frequencies/selected-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Com1),
+        "/instrumentation/comm[0]/frequencies/standby-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Com2),
+        "/instrumentation/comm[1]/frequencies/selected-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Com2),
+        "/instrumentation/comm[1]/frequencies/standby-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Nav1),
+        "/instrumentation/nav[0]/frequencies/selected-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Nav1),
+        "/instrumentation/nav[0]/frequencies/standby-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Nav2),
+        "/instrumentation/nav[1]/frequencies/selected-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Nav2),
+        "/instrumentation/nav[1]/frequencies/standby-mhz",
+        1_000_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Adf),
+        "/instrumentation/adf[0]/frequencies/selected-khz",
+        1_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Adf),
+        "/instrumentation/adf[0]/frequencies/standby-khz",
+        1_000.0,
+    ),
+    (
+        SimVariable::TransponderCode,
+        "/instrumentation/transponder/id-code",
+        1.0,
+    ),
+    (
+        SimVariable::TransponderMode,
+        "/instrumentation/transponder/inputs/knob-mode",
+        1.0, // numbered differently, see flightgear_transponder_mode
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Heading),
+        "/autopilot/settings/heading-bug-deg",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Altitude),
+        "/autopilot/settings/target-altitude-ft",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::VerticalSpeed),
+        "/autopilot/settings/vertical-speed-fpm",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Airspeed),
+        "/autopilot/settings/target-speed-kt",
+        1.0,
+    ),
+    (
+        SimVariable::Course(Radio::Nav1),
+        "/instrumentation/nav[0]/radials/selected-deg",
+        1.0,
+    ),
+    (
+        SimVariable::Course(Radio::Nav2),
+        "/instrumentation/nav[1]/radials/selected-deg",
+        1.0,
+    ),
+    (
+        SimVariable::Barometer,
+        "/instrumentation/altimeter/setting-hpa",
+        100.0,
+    ),
+    (
+        SimVariable::DmeDistance,
+        "/instrumentation/dme/indicated-distance-nm",
+        1.0,
+    ),
+];
+
+/// Lock property and the value it has while an autopilot mode is engaged
+/// The generic autopilot has no master switch, see toggle_autopilot
+const AUTOPILOT_LOCKS: [(AutopilotMode, &str, &str); 4] = [
+    (
+        AutopilotMode::Heading,
+        "/autopilot/locks/heading",
+        "dg-heading-hold",
+    ),
+    (
+        AutopilotMode::Altitude,
+        "/autopilot/locks/altitude",
+        "altitude-hold",
+    ),
+    (
+        AutopilotMode::VerticalSpeed,
+        "/autopilot/locks/altitude",
+        "vertical-speed-hold",
+    ),
+    (
+        AutopilotMode::Airspeed,
+        "/autopilot/locks/speed",
+        "speed-with-throttle",
+    ),
+];
+
+/// Heading lock of the generic autopilot's most basic mode
+const WING_LEVELER: &str = "wing-leveler";
+
+/// Set to true to press IDENT, and back to false to release it
+const IDENT_PROPERTY: &str = "/instrumentation/transponder/inputs/ident-btn";
+
+/// Read to check the connection, always present
+const HEARTBEAT_PROPERTY: &str = "/sim/time/elapsed-sec";
+
+/// "true" if the DME receives a station
+const DME_IN_RANGE_PROPERTY: &str = "/instrumentation/dme/in-range";
+
+/// FlightGear through its telnet property server
+/// Uses the "data" mode, where get only answers with the value and set doesn't answer at all
+pub struct FlightGearBackend {
+    address: SocketAddr,
+    connection: Option<(TcpStream, BufReader<TcpStream>)>,
+    values: HashMap<&'static str, String>, // every property as last read or set
+    read_at: Option<Instant>,
+    unanswered: usize, // gets whose answers haven't been read, e.g. after a timeout
+    ident_pressed_at: Option<Instant>,
+}
+
+impl FlightGearBackend {
+    pub fn new(address: SocketAddr) -> FlightGearBackend {
+        FlightGearBackend {
+            address,
+            connection: None,
+            values: HashMap::new(),
+            read_at: None,
+            unanswered: 0,
+            ident_pressed_at: None,
+        }
+    }
+
+    /// Send a single command, forgets the connection if FlightGear is gone
+    fn command(&mut self, command: &str) -> bool {
+        let written = match &mut self.connection {
+            Some((stream, _)) => stream.write_all(format!("{}\r\n", command).as_bytes()),
+            None => return false,
+        };
+        if written.is_err() {
+            self.connection = None;
+        }
+        self.connection.is_some()
+    }
+
+    /// Sets a property, an empty value clears it
+    fn set(&mut self, path: &'static str, value: String) -> bool {
+        let command = format!("set {} {}", path, value);
+        match value.is_empty() {
+            true => self.values.remove(path),
+            false => self.values.insert(path, value),
+        };
+        self.command(&command)
+    }
+
+    /// Returns the value of a property, None if it can't be read or doesn't exist
+    /// Read along with all the others, at most VALUES_MAX_AGE ago
+    fn get(&mut self, path: &str) -> Option<String> {
+        self.connection.as_ref()?;
+        if self
+            .read_at
+            .is_none_or(|read_at| read_at.elapsed() >= VALUES_MAX_AGE)
+        {
+            self.read_all();
+            self.read_at = Some(Instant::now()); // also after a timeout, not to wait on every get
+        }
+        self.values.get(path).cloned()
+    }
+
+    /// Asks for every property at once and waits for all the answers
+    /// Nothing is known until next time when FlightGear takes too long, e.g. while loading
+    fn read_all(&mut self) {
+        self.values.clear();
+
+        let mut paths: Vec<&'static str> = PROPERTIES.iter().map(|(_, path, _)| *path).collect();
+        paths.extend(AUTOPILOT_LOCKS.iter().map(|(_, path, _)| *path));
+        paths.extend([DME_IN_RANGE_PROPERTY, HEARTBEAT_PROPERTY]);
+        paths.sort_unstable();
+        paths.dedup();
+        let commands: String = paths
+            .iter()
+            .map(|path| format!("get {}\r\n", path))
+            .collect();
+        if !self.command(commands.trim_end()) {
+            return;
+        }
+        self.unanswered += paths.len();
+
+        // Answers to gets that timed out before come first
+        let stale = self.unanswered - paths.len();
+        for index in 0..self.unanswered {
+            let line = match self.read_line() {
+                Some(line) => line,
+                None => return,
+            };
+            self.unanswered -= 1;
+            if index >= stale && !line.is_empty() {
+                self.values.insert(paths[index - stale], line);
+            }
+        }
+    }
+
+    /// The next answer, None if there is none in time
+    /// Only forgets the connection if FlightGear closed it, a slow answer can still come
+    fn read_line(&mut self) -> Option<String> {
+        let (_, reader) = self.connection.as_mut()?;
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => {}
+            Ok(_) => return Some(line.trim().to_string()),
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return None
+            }
+            Err(_) => {}
+        }
+        self.connection = None;
+        None
+    }
+
+    /// Lets go of IDENT once it was pressed long enough
+    fn release_ident(&mut self) {
+        if self
+            .ident_pressed_at
+            .is_some_and(|pressed_at| pressed_at.elapsed() >= IDENT_PRESS_DURATION)
+        {
+            self.ident_pressed_at = None;
+            self.set(IDENT_PROPERTY, "false".to_string());
+        }
+    }
+
+    /// The property tree has no swap, so exchange the values as they are
+    fn swap(&mut self, radio: Radio) -> bool {
+        let (active_path, _) = property(SimVariable::ActiveFrequency(radio)).unwrap();
+        let (standby_path, _) = property(SimVariable::StandbyFrequency(radio)).unwrap();
+        match (self.get(active_path), self.get(standby_path)) {
+            (Some(active), Some(standby)) => {
+                self.set(active_path, standby) && self.set(standby_path, active)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the lock of a mode is set, the master counts as engaged while any lock is set
+    /// None if FlightGear can't be reached
+    fn autopilot_engaged(&mut self, mode: AutopilotMode) -> Option<bool> {
+        let mut engaged = false;
+        for (lock_mode, path, value) in AUTOPILOT_LOCKS {
+            if mode == lock_mode || mode == AutopilotMode::Master {
+                let lock = self.get(path);
+                self.connection.as_ref()?; // an empty lock reads as None as well
+                engaged |= match mode {
+                    AutopilotMode::Master => lock.is_some(),
+                    _ => lock.as_deref() == Some(value),
+                };
+            }
+        }
+        Some(engaged)
+    }
+
+    /// Sets or clears the lock of a mode
+    /// Disengaging the master clears all locks, engaging it levels the wings
+    fn toggle_autopilot(&mut self, mode: AutopilotMode) -> bool {
+        let engaged = match self.autopilot_engaged(mode) {
+            Some(engaged) => engaged,
+            None => return false,
+        };
+        if mode == AutopilotMode::Master {
+            return match engaged {
+                true => {
+                    let mut locks: Vec<&'static str> =
+                        AUTOPILOT_LOCKS.iter().map(|(_, path, _)| *path).collect();
+                    locks.dedup();
+                    locks.into_iter().all(|lock| self.set(lock, String::new()))
+                }
+                false => self.set(AUTOPILOT_LOCKS[0].1, WING_LEVELER.to_string()), // heading
+            };
+        }
+
+        let (_, path, value) = AUTOPILOT_LOCKS
+            .into_iter()
+            .find(|(lock_mode, _, _)| *lock_mode == mode)
+            .expect("every mode but the master has a lock");
+        let value = if engaged { "" } else { value };
+        self.set(path, value.to_string())
+    }
+}
+
+impl SimulatorBackend for FlightGearBackend {
+    fn connect(&mut self) -> bool {
+        let stream = match TcpStream::connect_timeout(&self.address, TIMEOUT) {
+            Ok(stream) => stream,
+            Err(_) => return false,
+        };
+        let reader = match stream.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(_) => return false,
+        };
+        if stream.set_read_timeout(Some(TIMEOUT)).is_err() {
+            return false;
+        }
+
+        self.connection = Some((stream, reader));
+        self.values.clear();
+        self.read_at = None;
+        self.unanswered = 0;
+        self.command("data")
+    }
+
+    fn send(&mut self, event: SimEvent) -> bool {
+        let (variable, value) = match event {
+            SimEvent::SwapFrequencies(radio) => return self.swap(radio),
+            SimEvent::TransponderIdent => {
+                self.ident_pressed_at = Some(Instant::now());
+                return self.set(IDENT_PROPERTY, "true".to_string());
+            }
+            SimEvent::ToggleAutopilot(mode) => return self.toggle_autopilot(mode),
+            SimEvent::TransponderMode(mode) => (
+                SimVariable::TransponderMode,
+                f64::from(flightgear_transponder_mode(mode)),
+            ),
+            _ => event.variable_value().expect("sets a property"),
+        };
+        match property(variable) {
+            Some((path, factor)) => self.set(path, (value / factor).to_string()),
+            None => self.connection.is_some(), // e.g. Mach, the generic autopilot has none
+        }
+    }
+
+    fn read_variable(&mut self, variable: SimVariable) -> Option<f64> {
+        self.release_ident();
+        if variable == SimVariable::DmeDistance
+            && !matches!(self.get(DME_IN_RANGE_PROPERTY)?.as_str(), "true" | "1")
+        {
+            return None;
+        }
+        if let SimVariable::AutopilotEngaged(mode) = variable {
+            return self.autopilot_engaged(mode).map(f64::from);
+        }
+
+        let (path, factor) = property(variable)?;
+        let value: f64 = self.get(path)?.parse().ok()?;
+        if variable == SimVariable::TransponderMode {
+            return TransponderMode::ALL
+                .into_iter()
+                .find(|mode| f64::from(flightgear_transponder_mode(*mode)) == value)
+                .map(|mode| f64::from(mode as u8));
+        }
+        Some(value * factor)
+    }
+
+    fn is_connected(&mut self) -> bool {
+        // Only a round trip tells whether FlightGear is still there, a slow one still is
+        self.release_ident();
+        self.get(HEARTBEAT_PROPERTY);
+        self.connection.is_some()
+    }
+
+    fn supports(&self, variable: SimVariable) -> bool {
+        // the generic autopilot has neither a Mach setting nor a flight path angle
+        !matches!(
+            variable,
+            SimVariable::AutopilotValue(AutopilotTarget::Mach | AutopilotTarget::FlightPathAngle)
+        )
+    }
+}
+
+/// Path and factor of the property holding a variable
+fn property(variable: SimVariable) -> Option<(&'static str, f64)> {
+    PROPERTIES
+        .iter()
+        .find(|(property_variable, _, _)| *property_variable == variable)
+        .map(|(_, path, factor)| (*path, *factor))
+}
+
+/// FlightGear's knob goes OFF, STANDBY, TEST, GROUND, ON, ALTITUDE
+fn flightgear_transponder_mode(mode: TransponderMode) -> u8 {
+    match mode {
+        TransponderMode::Off => 0,
+        TransponderMode::Standby => 1,
+        TransponderMode::On => 4,
+        TransponderMode::Altitude => 5,
+    }
+}
+
+#[cfg(test)]
+mod flightgear_tests {
+    use super::*;
+    use std::{collections::HashMap, net::TcpListener, thread};
+
+    /// Answers get commands from a fixed property tree, returns every other command received
+    /// Waits before the first answer, like FlightGear does while loading
+    fn fake_property_server(
+        properties: HashMap<&'static str, &'static str>,
+        first_answer_delay: Duration,
+    ) -> (SocketAddr, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            let mut commands = Vec::new();
+            let mut delay = Some(first_answer_delay);
+            for line in reader.lines() {
+                let line = line.unwrap();
+                match line.strip_prefix("get ") {
+                    Some(path) => {
+                        if let Some(delay) = delay.take() {
+                            thread::sleep(delay);
+                        }
+                        let value = properties.get(path).copied().unwrap_or("");
+                        stream
+                            .write_all(format!("{}\r\n", value).as_bytes())
+                            .unwrap();
+                    }
+                    None => commands.push(line),
+                }
+            }
+            commands
+        });
+
+        (address, server)
+    }
+
+    #[test]
+    fn test_against_fake_property_server() {
+        let (address, server) = fake_property_server(
+            HashMap::from([
+                (
+                    "/instrumentation/comm[0]/frequencies/selected-mhz",
+                    "118.25",
+                ),
+                ("/instrumentation/comm[0]/frequencies/standby-mhz", "121.5"),
+                ("/instrumentation/adf[0]/frequencies/standby-khz", "345"),
+                ("/autopilot/settings/vertical-speed-fpm", "-700"),
+                ("/instrumentation/dme/indicated-distance-nm", "12.3"),
+                ("/instrumentation/dme/in-range", "false"),
+                ("/instrumentation/transponder/inputs/knob-mode", "5"),
+                ("/autopilot/locks/altitude", "vertical-speed-hold"),
+            ]),
+            Duration::ZERO,
+        );
+        let mut backend = FlightGearBackend::new(address);
+
+        assert!(backend.connect());
+        assert_eq!(
+            backend.read_variable(SimVariable::ActiveFrequency(Radio::Com1)),
+            Some(118_250_000.0)
+        );
+        assert_eq!(
+            backend.read_variable(SimVariable::StandbyFrequency(Radio::Adf)),
+            Some(345_000.0)
+        );
+        assert_eq!(
+            backend.read_variable(SimVariable::AutopilotValue(AutopilotTarget::VerticalSpeed)),
+            Some(-700.0)
+        );
+        assert_eq!(backend.read_variable(SimVariable::DmeDistance), None); // out of range
+        assert_eq!(
+            backend.read_variable(SimVariable::TransponderMode),
+            Some(f64::from(TransponderMode::Altitude as u8))
+        );
+        assert_eq!(
+            backend.read_variable(SimVariable::AutopilotEngaged(AutopilotMode::Altitude)),
+            Some(0.0)
+        );
+        assert_eq!(
+            backend.read_variable(SimVariable::AutopilotEngaged(AutopilotMode::Master)),
+            Some(1.0)
+        );
+
+        assert!(backend.send(SimEvent::TransponderCode(7000)));
+        assert!(backend.send(SimEvent::TransponderMode(TransponderMode::Standby)));
+        assert!(backend.send(SimEvent::TransponderIdent));
+        assert!(backend.send(SimEvent::StandbyFrequency {
+            radio: Radio::Nav2,
+            frequency_hz: 110_300_000,
+        }));
+        assert!(backend.send(SimEvent::SwapFrequencies(Radio::Com1)));
+        assert!(backend.send(SimEvent::Barometer(101_325)));
+        assert!(backend.send(SimEvent::AutopilotValue {
+            target: AutopilotTarget::Mach,
+            value: 78,
+        }));
+        assert!(backend.send(SimEvent::ToggleAutopilot(AutopilotMode::Heading)));
+        assert!(backend.send(SimEvent::ToggleAutopilot(AutopilotMode::VerticalSpeed)));
+        assert_eq!(
+            backend.read_variable(SimVariable::ActiveFrequency(Radio::Com1)),
+            Some(121_500_000.0)
+        );
+        thread::sleep(IDENT_PRESS_DURATION);
+        assert!(backend.is_connected());
+        drop(backend); // closes the connection, ends the server
+
+        assert_eq!(
+            server.join().unwrap(),
+            vec![
+                "data",
+                "set /instrumentation/transponder/id-code 7000",
+                "set /instrumentation/transponder/inputs/knob-mode 1",
+                "set /instrumentation/transponder/inputs/ident-btn true",
+                "set /instrumentation/nav[1]/frequencies/standby-mhz 110.3",
+                "set /instrumentation/comm[0]/frequencies/selected-mhz 121.5",
+                "set /instrumentation/comm[0]/frequencies/standby-mhz 118.25",
+                "set /instrumentation/altimeter/setting-hpa 1013.25",
+                "set /autopilot/locks/heading dg-heading-hold",
+                "set /autopilot/locks/altitude ",
+                "set /instrumentation/transponder/inputs/ident-btn false",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slow_answer_keeps_the_connection() {
+        let (address, server) = fake_property_server(
+            HashMap::from([("/instrumentation/transponder/id-code", "7000")]),
+            TIMEOUT + Duration::from_millis(200),
+        );
+        let mut backend = FlightGearBackend::new(address);
+
+        assert!(backend.connect());
+        assert_eq!(backend.read_variable(SimVariable::TransponderCode), None);
+        assert!(backend.is_connected());
+
+        // the late answers are skipped, not taken for the next ones
+        thread::sleep(Duration::from_millis(200) + VALUES_MAX_AGE);
+        assert_eq!(
+            backend.read_variable(SimVariable::TransponderCode),
+            Some(7000.0)
+        );
+        drop(backend);
+        assert_eq!(server.join().unwrap(), vec!["data"]);
+    }
+
+    #[test]
+    fn test_no_flightgear_running() {
+        // nothing listens on a port that was just freed
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut backend = FlightGearBackend::new(address);
+        assert!(!backend.connect());
+        assert!(!backend.send(SimEvent::TransponderCode(7000)));
+        assert_eq!(backend.read_variable(SimVariable::TransponderCode), None);
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use super::{backend::*, tracking::swap_frequencies};
+
+/// In-process stand-in for a flight simulator
+/// Records every event sent and serves variables set by the test
+#[derive(Default)]
+pub struct MockSimulator {
+    pub events: Vec<SimEvent>,
+    pub variables: HashMap<SimVariable, f64>,
+    pub connected: bool,
+    pub unsupported: Vec<SimVariable>, // like a simulator without them
+    pub offline: bool, // refuses to connect, like a simulator that isn't started yet
+    pub connect_attempts: usize,
+}
+
+impl MockSimulator {
+    pub fn new() -> MockSimulator {
+        MockSimulator::default()
+    }
+
+    /// Returns and forgets the events sent so far
+    pub fn take_events(&mut self) -> Vec<SimEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl SimulatorBackend for MockSimulator {
+    fn connect(&mut self) -> bool {
+        self.connect_attempts += 1;
+        self.connected = !self.offline;
+        self.connected
+    }
+
+    fn send(&mut self, event: SimEvent) -> bool {
+        if !self.connected {
+            return false;
+        }
+
+        // Behave like the simulator, later reads return what was set
+        match event {
+            SimEvent::SwapFrequencies(radio) => swap_frequencies(&mut self.variables, radio),
+            SimEvent::ToggleAutopilot(mode) => {
+                let engaged = self
+                    .variables
+                    .entry(SimVariable::AutopilotEngaged(mode))
+                    .or_insert(0.0);
+                *engaged = if *engaged == 0.0 { 1.0 } else { 0.0 };
+            }
+            _ => {
+                if let Some((variable, value)) = event.variable_value() {
+                    self.variables.insert(variable, value);
+                }
+            }
+        }
+        self.events.push(event);
+        true
+    }
+
+    fn read_variable(&mut self, variable: SimVariable) -> Option<f64> {
+        if !self.connected {
+            return None;
+        }
+        self.variables.get(&variable).copied()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn supports(&self, variable: SimVariable) -> bool {
+        !self.unsupported.contains(&variable)
+    }
+}
@@ -0,0 +1,8 @@
+pub mod backend;
+pub mod flightgear;
+#[cfg(test)]
+pub mod mock;
+#[cfg(windows)]
+pub mod msfs;
+pub mod tracking;
+pub mod xplane;
@@ -0,0 +1,563 @@
+use parse_int::parse;
+use simconnect::{DispatchResult, SimConnector};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::backend::*;
+use crate::radio_panel::frequency::{Band, Frequency};
+
+/* User defined event IDs for MSFS */
+const EVENT_ID_COM_RADIO_SET_HZ: u32 = 1000;
+const EVENT_ID_COM_STBY_RADIO_SET_HZ: u32 = 1001;
+const EVENT_ID_COM2_RADIO_SET_HZ: u32 = 1002;
+const EVENT_ID_COM2_STBY_RADIO_SET_HZ: u32 = 1003;
+const EVENT_ID_NAV1_RADIO_SET_HZ: u32 = 1004;
+const EVENT_ID_NAV1_STBY_SET_HZ: u32 = 1005;
+const EVENT_ID_NAV2_RADIO_SET_HZ: u32 = 1006;
+const EVENT_ID_NAV2_STBY_SET_HZ: u32 = 1007;
+const EVENT_ID_XPNDR_SET: u32 = 1008;
+const EVENT_ID_HEADING_BUG_SET: u32 = 1009;
+const EVENT_ID_AP_ALT_VAR_SET_ENGLISH: u32 = 1010;
+const EVENT_ID_AP_VS_VAR_SET_ENGLISH: u32 = 1011;
+const EVENT_ID_AP_SPD_VAR_SET: u32 = 1012;
+const EVENT_ID_COM_STBY_RADIO_SWAP: u32 = 1013;
+const EVENT_ID_COM2_RADIO_SWAP: u32 = 1014;
+const EVENT_ID_NAV1_RADIO_SWAP: u32 = 1015;
+const EVENT_ID_NAV2_RADIO_SWAP: u32 = 1016;
+const EVENT_ID_ADF_COMPLETE_SET: u32 = 1017;
+const EVENT_ID_ADF_STBY_SET: u32 = 1018;
+const EVENT_ID_ADF1_RADIO_SWAP: u32 = 1019;
+const EVENT_ID_XPNDR_IDENT_ON: u32 = 1020;
+const EVENT_ID_AP_MASTER: u32 = 1021;
+const EVENT_ID_AP_HDG_HOLD: u32 = 1022;
+const EVENT_ID_AP_ALT_HOLD: u32 = 1023;
+const EVENT_ID_AP_VS_HOLD: u32 = 1024;
+const EVENT_ID_AP_PANEL_SPEED_HOLD: u32 = 1025;
+const EVENT_ID_VOR1_SET: u32 = 1026;
+const EVENT_ID_VOR2_SET: u32 = 1027;
+const EVENT_ID_KOHLSMAN_SET: u32 = 1028;
+const EVENT_ID_AP_MACH_VAR_SET: u32 = 1029;
+
+/* System event IDs */
+const EVENT_ID_ONE_SECOND: u32 = 2000;
+
+/// SimConnect reports a closed pipe the same way as an empty queue,
+/// so FS2020 counts as gone after this long without the one second system event
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Simulation variables the panel subscribes to and their units
+/// The position is used as data definition and request ID
+const SIMVARS: [(SimVariable, &str, &str); 26] = [
+    (
+        SimVariable::ActiveFrequency(Radio::Com1),
+        "COM ACTIVE FREQUENCY:1",
+        "Hz",
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Com1),
+        "COM STANDBY FREQUENCY:1",
+        "Hz",
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Com2),
+        "COM ACTIVE FREQUENCY:2",
+        "Hz",
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Com2),
+        "COM STANDBY FREQUENCY:2",
+        "Hz",
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Nav1),
+        "NAV ACTIVE FREQUENCY:1",
+        "Hz",
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Nav1),
+        "NAV STANDBY FREQUENCY:1",
+        "Hz",
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Nav2),
+        "NAV ACTIVE FREQUENCY:2",
+        "Hz",
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Nav2),
+        "NAV STANDBY FREQUENCY:2",
+        "Hz",
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Adf),
+        "ADF ACTIVE FREQUENCY:1",
+        "Hz",
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Adf),
+        "ADF STANDBY FREQUENCY:1",
+        "Hz",
+    ),
+    (SimVariable::TransponderCode, "TRANSPONDER CODE:1", "Bco16"),
+    (SimVariable::TransponderMode, "TRANSPONDER STATE:1", "Enum"),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Heading),
+        "AUTOPILOT HEADING LOCK DIR",
+        "Degrees",
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Altitude),
+        "AUTOPILOT ALTITUDE LOCK VAR",
+        "Feet",
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::VerticalSpeed),
+        "AUTOPILOT VERTICAL HOLD VAR",
+        "Feet/minute",
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Airspeed),
+        "AUTOPILOT AIRSPEED HOLD VAR",
+        "Knots",
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Mach),
+        "AUTOPILOT MACH HOLD VAR",
+        "Number",
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Master),
+        "AUTOPILOT MASTER",
+        "Bool",
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Heading),
+        "AUTOPILOT HEADING LOCK",
+        "Bool",
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Altitude),
+        "AUTOPILOT ALTITUDE LOCK",
+        "Bool",
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::VerticalSpeed),
+        "AUTOPILOT VERTICAL HOLD",
+        "Bool",
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Airspeed),
+        "AUTOPILOT AIRSPEED HOLD",
+        "Bool",
+    ),
+    (SimVariable::Course(Radio::Nav1), "NAV OBS:1", "Degrees"),
+    (SimVariable::Course(Radio::Nav2), "NAV OBS:2", "Degrees"),
+    (SimVariable::Barometer, "KOHLSMAN SETTING MB:1", "Millibars"),
+    (SimVariable::DmeDistance, "HSI DISTANCE", "Nautical miles"),
+];
+
+/// Microsoft Flight Simulator 2020 through SimConnect
+pub struct SimConnectBackend {
+    simulator: SimConnector,
+    values: HashMap<SimVariable, f64>, // latest value of every simulation variable received
+    quit: bool,                        // FS2020 said goodbye or stopped answering
+    last_message: Instant,
+}
+
+impl SimConnectBackend {
+    pub fn new() -> SimConnectBackend {
+        SimConnectBackend {
+            simulator: SimConnector::new(),
+            values: HashMap::new(),
+            quit: false,
+            last_message: Instant::now(),
+        }
+    }
+
+    fn setup_event_ids(&self) {
+        // Tell simulator which event ID is supposed to represent what simulator event
+        for (event_id, event_name) in [
+            (EVENT_ID_COM_RADIO_SET_HZ, "COM_RADIO_SET_HZ"),
+            (EVENT_ID_COM_STBY_RADIO_SET_HZ, "COM_STBY_RADIO_SET_HZ"),
+            (EVENT_ID_COM2_RADIO_SET_HZ, "COM2_RADIO_SET_HZ"),
+            (EVENT_ID_COM2_STBY_RADIO_SET_HZ, "COM2_STBY_RADIO_SET_HZ"),
+            (EVENT_ID_NAV1_RADIO_SET_HZ, "NAV1_RADIO_SET_HZ"),
+            (EVENT_ID_NAV1_STBY_SET_HZ, "NAV1_STBY_SET_HZ"),
+            (EVENT_ID_NAV2_RADIO_SET_HZ, "NAV2_RADIO_SET_HZ"),
+            (EVENT_ID_NAV2_STBY_SET_HZ, "NAV2_STBY_SET_HZ"),
+            (EVENT_ID_XPNDR_SET, "XPNDR_SET"),
+            (EVENT_ID_HEADING_BUG_SET, "HEADING_BUG_SET"),
+            (EVENT_ID_AP_ALT_VAR_SET_ENGLISH, "AP_ALT_VAR_SET_ENGLISH"),
+            (EVENT_ID_AP_VS_VAR_SET_ENGLISH, "AP_VS_VAR_SET_ENGLISH"),
+            (EVENT_ID_AP_SPD_VAR_SET, "AP_SPD_VAR_SET"),
+            (EVENT_ID_COM_STBY_RADIO_SWAP, "COM_STBY_RADIO_SWAP"),
+            (EVENT_ID_COM2_RADIO_SWAP, "COM2_RADIO_SWAP"),
+            (EVENT_ID_NAV1_RADIO_SWAP, "NAV1_RADIO_SWAP"),
+            (EVENT_ID_NAV2_RADIO_SWAP, "NAV2_RADIO_SWAP"),
+            (EVENT_ID_ADF_COMPLETE_SET, "ADF_COMPLETE_SET"),
+            (EVENT_ID_ADF_STBY_SET, "ADF_STBY_SET"),
+            (EVENT_ID_ADF1_RADIO_SWAP, "ADF1_RADIO_SWAP"),
+            (EVENT_ID_XPNDR_IDENT_ON, "XPNDR_IDENT_ON"),
+            (EVENT_ID_AP_MASTER, "AP_MASTER"),
+            (EVENT_ID_AP_HDG_HOLD, "AP_HDG_HOLD"),
+            (EVENT_ID_AP_ALT_HOLD, "AP_ALT_HOLD"),
+            (EVENT_ID_AP_VS_HOLD, "AP_VS_HOLD"),
+            (EVENT_ID_AP_PANEL_SPEED_HOLD, "AP_PANEL_SPEED_HOLD"),
+            (EVENT_ID_VOR1_SET, "VOR1_SET"),
+            (EVENT_ID_VOR2_SET, "VOR2_SET"),
+            (EVENT_ID_KOHLSMAN_SET, "KOHLSMAN_SET"),
+            (EVENT_ID_AP_MACH_VAR_SET, "AP_MACH_VAR_SET"),
+        ] {
+            self.simulator
+                .map_client_event_to_sim_event(event_id, event_name);
+        }
+    }
+
+    /// Have SimConnect send every simulation variable whenever it changes
+    fn subscribe_variables(&self) {
+        for (index, (_, name, unit)) in SIMVARS.iter().enumerate() {
+            let id = index as u32;
+            self.simulator.add_data_definition(
+                id,
+                name,
+                unit,
+                simconnect::SIMCONNECT_DATATYPE_SIMCONNECT_DATATYPE_FLOAT64,
+                u32::MAX,
+                0.0,
+            );
+            self.simulator.request_data_on_sim_object(
+                id,
+                id,
+                simconnect::SIMCONNECT_OBJECT_ID_USER,
+                simconnect::SIMCONNECT_PERIOD_SIMCONNECT_PERIOD_SIM_FRAME,
+                simconnect::SIMCONNECT_DATA_REQUEST_FLAG_CHANGED,
+                0,
+                0,
+                0,
+            );
+        }
+    }
+
+    /// Set a subscribed simulation variable directly, for values without a key event
+    fn set_variable(&self, variable: SimVariable, value: f64) -> bool {
+        let id = SIMVARS
+            .iter()
+            .position(|(simvar, _, _)| *simvar == variable)
+            .expect("every variable set is subscribed") as u32;
+        let mut value = value;
+        // SAFETY: the pointer is to a local f64 that outlives the call, and the size passed is
+        // that of an f64, which is how every variable is defined in subscribe_variables
+        unsafe {
+            self.simulator.set_data_on_sim_object(
+                id,
+                simconnect::SIMCONNECT_OBJECT_ID_USER,
+                0,
+                0,
+                std::mem::size_of::<f64>() as u32,
+                std::ptr::addr_of_mut!(value) as *mut std::ffi::c_void,
+            )
+        }
+    }
+
+    /// Take in every value update SimConnect has queued up
+    fn receive_values(&mut self) {
+        loop {
+            let message = self.simulator.get_next_message();
+            if message.is_ok() {
+                self.last_message = Instant::now();
+            }
+            match message {
+                Ok(DispatchResult::Quit(_)) => self.quit = true,
+                Ok(DispatchResult::SimObjectData(data)) => {
+                    let value = unsafe {
+                        let value_ptr = std::ptr::addr_of!(data.dwData) as *const f64;
+                        std::ptr::read_unaligned(value_ptr)
+                    };
+                    if let Some((variable, _, _)) = SIMVARS.get(data.dwRequestID as usize) {
+                        self.values.insert(*variable, value);
+                    }
+                }
+                Ok(_) => (), // events, exceptions, ...
+                Err(_) => {
+                    // nothing queued anymore, or the pipe is closed if it stays that way
+                    if self.last_message.elapsed() > HEARTBEAT_TIMEOUT {
+                        self.quit = true;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl SimulatorBackend for SimConnectBackend {
+    fn connect(&mut self) -> bool {
+        if !self.simulator.connect("BetterRadioPanel") {
+            return false;
+        }
+        self.setup_event_ids();
+        self.subscribe_variables();
+        self.simulator
+            .subscribe_to_system_event(EVENT_ID_ONE_SECOND, "1sec");
+        self.values.clear();
+        self.quit = false;
+        self.last_message = Instant::now();
+        true
+    }
+
+    fn send(&mut self, event: SimEvent) -> bool {
+        // No key event sets the mode in every aircraft
+        if let SimEvent::TransponderMode(mode) = event {
+            return self.set_variable(SimVariable::TransponderMode, f64::from(mode as u8));
+        }
+
+        match client_event(event) {
+            Some((event_id, value)) => {
+                self.simulator
+                    .transmit_client_event(1, event_id, value, 5, 0)
+                    && self.is_connected()
+            }
+            // e.g. a flight path angle, nothing to send
+            None => self.is_connected(),
+        }
+    }
+
+    fn read_variable(&mut self, variable: SimVariable) -> Option<f64> {
+        self.receive_values();
+        let value = *self.values.get(&variable)?;
+        match variable {
+            SimVariable::TransponderCode => Some(f64::from(transponder_from_bcd(value as u32))),
+            SimVariable::DmeDistance => valid_dme_distance(value),
+            SimVariable::AutopilotValue(AutopilotTarget::Mach) => Some(value * 100.0),
+            SimVariable::Barometer => Some(value * 100.0), // from millibars
+            _ => Some(value),
+        }
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.receive_values();
+        !self.quit
+    }
+
+    fn supports(&self, variable: SimVariable) -> bool {
+        variable != SimVariable::AutopilotValue(AutopilotTarget::FlightPathAngle)
+    }
+}
+
+/// The client event ID and value SimConnect expects for an event
+/// None for the events FS2020 has no client event for
+fn client_event(event: SimEvent) -> Option<(u32, u32)> {
+    let client_event = match event {
+        SimEvent::ActiveFrequency {
+            radio,
+            frequency_hz,
+        } => {
+            let event_id = match radio {
+                Radio::Com1 => EVENT_ID_COM_RADIO_SET_HZ,
+                Radio::Com2 => EVENT_ID_COM2_RADIO_SET_HZ,
+                Radio::Nav1 => EVENT_ID_NAV1_RADIO_SET_HZ,
+                Radio::Nav2 => EVENT_ID_NAV2_RADIO_SET_HZ,
+                Radio::Adf => {
+                    return Some((
+                        EVENT_ID_ADF_COMPLETE_SET,
+                        Frequency::closest(Band::Adf, frequency_hz).bcd(),
+                    ))
+                }
+            };
+            (event_id, frequency_hz)
+        }
+        SimEvent::StandbyFrequency {
+            radio,
+            frequency_hz,
+        } => {
+            let event_id = match radio {
+                Radio::Com1 => EVENT_ID_COM_STBY_RADIO_SET_HZ,
+                Radio::Com2 => EVENT_ID_COM2_STBY_RADIO_SET_HZ,
+                Radio::Nav1 => EVENT_ID_NAV1_STBY_SET_HZ,
+                Radio::Nav2 => EVENT_ID_NAV2_STBY_SET_HZ,
+                Radio::Adf => {
+                    return Some((
+                        EVENT_ID_ADF_STBY_SET,
+                        Frequency::closest(Band::Adf, frequency_hz).bcd(),
+                    ))
+                }
+            };
+            (event_id, frequency_hz)
+        }
+        SimEvent::TransponderCode(code) => (EVENT_ID_XPNDR_SET, bcd(u32::from(code))),
+        SimEvent::TransponderMode(_) => return None, // set as a simulation variable
+        SimEvent::TransponderIdent => (EVENT_ID_XPNDR_IDENT_ON, 0),
+        SimEvent::AutopilotValue { target, value } => {
+            let event_id = match target {
+                AutopilotTarget::Heading => EVENT_ID_HEADING_BUG_SET,
+                AutopilotTarget::Altitude => EVENT_ID_AP_ALT_VAR_SET_ENGLISH,
+                AutopilotTarget::VerticalSpeed => EVENT_ID_AP_VS_VAR_SET_ENGLISH,
+                AutopilotTarget::Airspeed => EVENT_ID_AP_SPD_VAR_SET,
+                AutopilotTarget::Mach => EVENT_ID_AP_MACH_VAR_SET,
+                AutopilotTarget::FlightPathAngle => return None,
+            };
+            (event_id, value as u32) // negative values as two's complement
+        }
+        SimEvent::Course { radio, degrees } => {
+            let event_id = match radio {
+                Radio::Nav1 => EVENT_ID_VOR1_SET,
+                Radio::Nav2 => EVENT_ID_VOR2_SET,
+                _ => return None, // only NAV radios have a course
+            };
+            (event_id, u32::from(degrees))
+        }
+        SimEvent::Barometer(pascal) => (EVENT_ID_KOHLSMAN_SET, pascal * 16 / 100), // 1/16 millibars
+        SimEvent::ToggleAutopilot(mode) => {
+            let event_id = match mode {
+                AutopilotMode::Master => EVENT_ID_AP_MASTER,
+                AutopilotMode::Heading => EVENT_ID_AP_HDG_HOLD,
+                AutopilotMode::Altitude => EVENT_ID_AP_ALT_HOLD,
+                AutopilotMode::VerticalSpeed => EVENT_ID_AP_VS_HOLD,
+                AutopilotMode::Airspeed => EVENT_ID_AP_PANEL_SPEED_HOLD,
+            };
+            (event_id, 0) // without a value these toggle
+        }
+        SimEvent::SwapFrequencies(radio) => {
+            let event_id = match radio {
+                Radio::Com1 => EVENT_ID_COM_STBY_RADIO_SWAP,
+                Radio::Com2 => EVENT_ID_COM2_RADIO_SWAP,
+                Radio::Nav1 => EVENT_ID_NAV1_RADIO_SWAP,
+                Radio::Nav2 => EVENT_ID_NAV2_RADIO_SWAP,
+                Radio::Adf => EVENT_ID_ADF1_RADIO_SWAP,
+            };
+            (event_id, 0)
+        }
+    };
+    Some(client_event)
+}
+
+/// Binary coded decimal, as FS2020 expects transponder codes and ADF frequencies
+/// e.g. 7421 is 0x7421
+fn bcd(value: u32) -> u32 {
+    parse::<u32>(&format!("0x{}", value)).unwrap()
+}
+
+/// FS2020 reports the code as binary coded decimal, e.g. 0x7000
+fn transponder_from_bcd(bcd: u32) -> u16 {
+    format!("{:x}", bcd).parse().unwrap_or(0)
+}
+
+fn valid_dme_distance(distance: f64) -> Option<f64> {
+    let distance = distance.abs();
+    // if wrong CDI mode is selected, MSFS returns invalid values
+    if distance == 1.0 || distance < 0.01 {
+        None
+    } else {
+        Some(distance)
+    }
+}
+
+#[cfg(test)]
+mod simconnect_tests {
+    use super::*;
+
+    #[test]
+    fn test_frequencies() {
+        assert_eq!(
+            client_event(SimEvent::StandbyFrequency {
+                radio: Radio::Nav2,
+                frequency_hz: 110_500_000,
+            }),
+            Some((EVENT_ID_NAV2_STBY_SET_HZ, 110_500_000))
+        );
+    }
+
+    #[test]
+    fn test_adf_is_bcd_hz() {
+        assert_eq!(
+            client_event(SimEvent::ActiveFrequency {
+                radio: Radio::Adf,
+                frequency_hz: 345_500,
+            }),
+            Some((EVENT_ID_ADF_COMPLETE_SET, 0x0034_5500))
+        );
+        assert_eq!(
+            client_event(SimEvent::StandbyFrequency {
+                radio: Radio::Adf,
+                frequency_hz: 1_799_000,
+            }),
+            Some((EVENT_ID_ADF_STBY_SET, 0x0179_9000))
+        );
+    }
+
+    #[test]
+    fn test_native_swap() {
+        assert_eq!(
+            client_event(SimEvent::SwapFrequencies(Radio::Com1)),
+            Some((EVENT_ID_COM_STBY_RADIO_SWAP, 0))
+        );
+    }
+
+    #[test]
+    fn test_transponder_is_bcd() {
+        assert_eq!(
+            client_event(SimEvent::TransponderCode(7421)),
+            Some((EVENT_ID_XPNDR_SET, 0x7421))
+        );
+        assert_eq!(
+            client_event(SimEvent::TransponderCode(200)),
+            Some((EVENT_ID_XPNDR_SET, 0x0200))
+        );
+    }
+
+    #[test]
+    fn test_events_without_client_event() {
+        assert_eq!(
+            client_event(SimEvent::TransponderMode(TransponderMode::Altitude)),
+            None
+        );
+        assert_eq!(
+            client_event(SimEvent::AutopilotValue {
+                target: AutopilotTarget::FlightPathAngle,
+                value: -30,
+            }),
+            None
+        );
+        assert_eq!(
+            client_event(SimEvent::Course {
+                radio: Radio::Com1,
+                degrees: 90,
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transponder_readback() {
+        assert_eq!(transponder_from_bcd(0x7421), 7421);
+        assert_eq!(transponder_from_bcd(0x0200), 200);
+    }
+
+    #[test]
+    fn test_invalid_dme_distance() {
+        assert_eq!(valid_dme_distance(-12.5), Some(12.5));
+        assert_eq!(valid_dme_distance(1.0), None);
+        assert_eq!(valid_dme_distance(0.0), None);
+    }
+
+    #[test]
+    fn test_barometer_in_sixteenths_of_millibars() {
+        assert_eq!(
+            client_event(SimEvent::Barometer(101_325)),
+            Some((EVENT_ID_KOHLSMAN_SET, 16_212))
+        );
+    }
+
+    #[test]
+    fn test_negative_vertical_speed() {
+        let (event_id, value) = client_event(SimEvent::AutopilotValue {
+            target: AutopilotTarget::VerticalSpeed,
+            value: -500,
+        })
+        .unwrap();
+        assert_eq!(event_id, EVENT_ID_AP_VS_VAR_SET_ENGLISH);
+        assert_eq!(value as i32, -500);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use super::backend::*;
+
+/// Only passes events on that change a value in the simulator
+/// Values read from the simulator count as already set, so they are never sent back
+/// Nothing is sent for a value the simulator hasn't reported yet, the panel only has a default for it
+pub struct ChangeTracker<B: SimulatorBackend> {
+    pub backend: B,
+    known_values: HashMap<SimVariable, f64>, // what the simulator has, as far as known
+}
+
+impl<B: SimulatorBackend> ChangeTracker<B> {
+    pub fn new(backend: B) -> ChangeTracker<B> {
+        ChangeTracker {
+            backend,
+            known_values: HashMap::new(),
+        }
+    }
+}
+
+impl<B: SimulatorBackend> SimulatorBackend for ChangeTracker<B> {
+    fn connect(&mut self) -> bool {
+        self.known_values.clear(); // could be another flight or aircraft by now
+        self.backend.connect()
+    }
+
+    fn send(&mut self, event: SimEvent) -> bool {
+        let (variable, value) = match event.variable_value() {
+            Some(variable_value) => variable_value,
+            None => {
+                // Always sent, it changes something every time
+                let sent = self.backend.send(event);
+                if let (true, SimEvent::SwapFrequencies(radio)) = (sent, event) {
+                    swap_frequencies(&mut self.known_values, radio);
+                }
+                return sent;
+            }
+        };
+        // e.g. right after connecting, some simulators report their values a bit later
+        let unchanged = self
+            .known_values
+            .get(&variable)
+            .is_none_or(|known| same_value(variable, *known, value));
+        if unchanged {
+            return self.backend.is_connected();
+        }
+
+        let sent = self.backend.send(event);
+        if sent {
+            self.known_values.insert(variable, value);
+        }
+        sent
+    }
+
+    fn read_variable(&mut self, variable: SimVariable) -> Option<f64> {
+        let value = self.backend.read_variable(variable);
+        if let Some(value) = value {
+            self.known_values.insert(variable, value);
+        }
+        value
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.backend.is_connected()
+    }
+
+    fn supports(&self, variable: SimVariable) -> bool {
+        self.backend.supports(variable)
+    }
+}
+
+/// Exchange active and standby values of a radio, also when only one of them is there
+pub fn swap_frequencies(values: &mut HashMap<SimVariable, f64>, radio: Radio) {
+    let active = values.remove(&SimVariable::ActiveFrequency(radio));
+    let standby = values.remove(&SimVariable::StandbyFrequency(radio));
+    if let Some(standby) = standby {
+        values.insert(SimVariable::ActiveFrequency(radio), standby);
+    }
+    if let Some(active) = active {
+        values.insert(SimVariable::StandbyFrequency(radio), active);
+    }
+}
+
+/// Differences the panel can't show don't count, e.g. parts of a kHz or of a degree
+fn same_value(variable: SimVariable, a: f64, b: f64) -> bool {
+    let resolution = match variable {
+        SimVariable::ActiveFrequency(Radio::Adf) | SimVariable::StandbyFrequency(Radio::Adf) => {
+            100.0 // ADF can be tuned in 0.5 kHz steps
+        }
+        SimVariable::ActiveFrequency(_) | SimVariable::StandbyFrequency(_) => 1000.0,
+        _ => 1.0,
+    };
+    (a - b).abs() < resolution / 2.0
+}
+
+#[cfg(test)]
+mod tracking_tests {
+    use super::*;
+    use crate::simulator::mock::MockSimulator;
+
+    fn heading(value: i32) -> SimEvent {
+        SimEvent::AutopilotValue {
+            target: AutopilotTarget::Heading,
+            value,
+        }
+    }
+
+    /// A tracker knowing the simulator's heading
+    fn tracker_with_heading(value: f64) -> ChangeTracker<MockSimulator> {
+        let mut tracker = ChangeTracker::new(MockSimulator::new());
+        assert!(tracker.connect());
+        let variable = SimVariable::AutopilotValue(AutopilotTarget::Heading);
+        tracker.backend.variables.insert(variable, value);
+        tracker.read_variable(variable);
+        tracker
+    }
+
+    #[test]
+    fn test_unchanged_values_are_not_sent() {
+        let mut tracker = tracker_with_heading(80.0);
+
+        assert!(tracker.send(heading(90)));
+        assert!(tracker.send(heading(90)));
+        assert!(tracker.send(heading(91)));
+        assert_eq!(
+            tracker.backend.take_events(),
+            vec![heading(90), heading(91)]
+        );
+    }
+
+    #[test]
+    fn test_values_not_reported_yet_are_not_sent() {
+        let mut tracker = ChangeTracker::new(MockSimulator::new());
+        tracker.connect();
+        assert!(tracker.send(heading(90)));
+        assert!(tracker.backend.take_events().is_empty());
+
+        tracker
+            .backend
+            .variables
+            .insert(SimVariable::AutopilotValue(AutopilotTarget::Heading), 0.0);
+        tracker.read_variable(SimVariable::AutopilotValue(AutopilotTarget::Heading));
+        tracker.send(heading(90));
+        assert_eq!(tracker.backend.take_events(), vec![heading(90)]);
+    }
+
+    #[test]
+    fn test_values_read_are_not_sent_back() {
+        let mut tracker = ChangeTracker::new(MockSimulator::new());
+        tracker.connect();
+        tracker.backend.variables.insert(
+            SimVariable::StandbyFrequency(Radio::Com1),
+            118_008_333.0, // 8.33 kHz channel, shown as 118.008
+        );
+
+        tracker.read_variable(SimVariable::StandbyFrequency(Radio::Com1));
+        tracker.send(SimEvent::StandbyFrequency {
+            radio: Radio::Com1,
+            frequency_hz: 118_008_000,
+        });
+        assert!(tracker.backend.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_swap_is_not_followed_by_sets() {
+        let mut tracker = ChangeTracker::new(MockSimulator::new());
+        tracker.connect();
+        let com1 = |active, standby| {
+            [
+                SimEvent::ActiveFrequency {
+                    radio: Radio::Com1,
+                    frequency_hz: active,
+                },
+                SimEvent::StandbyFrequency {
+                    radio: Radio::Com1,
+                    frequency_hz: standby,
+                },
+            ]
+        };
+        for (variable, value) in [
+            (SimVariable::ActiveFrequency(Radio::Com1), 118_000_000.0),
+            (SimVariable::StandbyFrequency(Radio::Com1), 121_500_000.0),
+        ] {
+            tracker.backend.variables.insert(variable, value);
+            tracker.read_variable(variable);
+        }
+
+        // the panel swaps locally and then sends both values as usual
+        tracker.send(SimEvent::SwapFrequencies(Radio::Com1));
+        for event in com1(121_500_000, 118_000_000) {
+            tracker.send(event);
+        }
+        assert_eq!(
+            tracker.backend.take_events(),
+            vec![SimEvent::SwapFrequencies(Radio::Com1)]
+        );
+    }
+
+    #[test]
+    fn test_lost_connection_is_noticed_without_sending() {
+        let mut tracker = tracker_with_heading(80.0);
+        tracker.send(heading(90));
+
+        tracker.backend.connected = false;
+        assert!(!tracker.send(heading(90)));
+    }
+
+    #[test]
+    fn test_nothing_is_known_after_reconnecting() {
+        let mut tracker = tracker_with_heading(80.0);
+        tracker.send(heading(90));
+        // could be another aircraft by now, its heading is reported again first
+        tracker.backend.variables.clear();
+        tracker.connect();
+        tracker.send(heading(100));
+        assert_eq!(tracker.backend.take_events(), vec![heading(90)]);
+    }
+}
@@ -0,0 +1,527 @@
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::backend::*;
+
+/// Where X-Plane listens for UDP packets by default
+pub const XPLANE_DEFAULT_PORT: u16 = 49000;
+
+/// How often X-Plane sends each subscribed dataref
+const RREF_FREQUENCY: i32 = 10;
+
+/// How long to wait for the first values after subscribing
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Without any values for this long, X-Plane is considered gone
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+const RREF_PATH_SIZE: usize = 400;
+const DREF_PATH_SIZE: usize = 500;
+
+/// Every dataref the panel subscribes to
+/// Value in X-Plane times the factor is the value in the unit of the SimVariable
+const DATAREFS: [(SimVariable, &str, f64); 27] = [
+    (
+        SimVariable::ActiveFrequency(Radio::Com1),
+        "sim/cockpit2/radios/actuators/com1_frequency_hz_833",
+        1_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Com1),
+        "sim/cockpit2/radios/actuators/com1_standby_frequency_hz_833",
+        1_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Com2),
+        "sim/cockpit2/radios/actuators/com2_frequency_hz_833",
+        1_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Com2),
+        "sim/cockpit2/radios/actuators/com2_standby_frequency_hz_833",
+        1_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Nav1),
+        "sim/cockpit2/radios/actuators/nav1_frequency_hz",
+        10_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Nav1),
+        "sim/cockpit2/radios/actuators/nav1_standby_frequency_hz",
+        10_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Nav2),
+        "sim/cockpit2/radios/actuators/nav2_frequency_hz",
+        10_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Nav2),
+        "sim/cockpit2/radios/actuators/nav2_standby_frequency_hz",
+        10_000.0,
+    ),
+    (
+        SimVariable::ActiveFrequency(Radio::Adf),
+        "sim/cockpit2/radios/actuators/adf1_frequency_hz",
+        1_000.0,
+    ),
+    (
+        SimVariable::StandbyFrequency(Radio::Adf),
+        "sim/cockpit2/radios/actuators/adf1_standby_frequency_hz",
+        1_000.0,
+    ),
+    (
+        SimVariable::TransponderCode,
+        "sim/cockpit2/radios/actuators/transponder_code",
+        1.0,
+    ),
+    (
+        SimVariable::TransponderMode,
+        "sim/cockpit2/radios/actuators/transponder_mode",
+        1.0, // numbered differently, see xplane_transponder_mode
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Heading),
+        "sim/cockpit2/autopilot/heading_dial_deg_mag_pilot",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Altitude),
+        "sim/cockpit2/autopilot/altitude_dial_ft",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::VerticalSpeed),
+        "sim/cockpit2/autopilot/vvi_dial_fpm",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Airspeed),
+        "sim/cockpit2/autopilot/airspeed_dial_kts",
+        1.0, // in knots also while the dial is in Mach mode
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::Mach),
+        "sim/cockpit2/autopilot/airspeed_dial_mach",
+        100.0,
+    ),
+    (
+        SimVariable::AutopilotValue(AutopilotTarget::FlightPathAngle),
+        "sim/cockpit2/autopilot/fpa",
+        10.0,
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Master),
+        "sim/cockpit2/autopilot/servos_on",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Heading),
+        "sim/cockpit2/autopilot/heading_status",
+        1.0, // 1 when armed, 2 when captured
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Altitude),
+        "sim/cockpit2/autopilot/altitude_hold_status",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::VerticalSpeed),
+        "sim/cockpit2/autopilot/vvi_status",
+        1.0,
+    ),
+    (
+        SimVariable::AutopilotEngaged(AutopilotMode::Airspeed),
+        "sim/cockpit2/autopilot/speed_status",
+        1.0,
+    ),
+    (
+        SimVariable::Course(Radio::Nav1),
+        "sim/cockpit2/radios/actuators/nav1_obs_deg_mag_pilot",
+        1.0,
+    ),
+    (
+        SimVariable::Course(Radio::Nav2),
+        "sim/cockpit2/radios/actuators/nav2_obs_deg_mag_pilot",
+        1.0,
+    ),
+    (
+        SimVariable::Barometer,
+        "sim/cockpit2/gauges/actuators/barometer_setting_in_hg_pilot",
+        3_386.39, // pascal per inHg
+    ),
+    (
+        SimVariable::DmeDistance,
+        "sim/cockpit2/radios/indicators/nav1_dme_distance_nm",
+        1.0,
+    ),
+];
+
+/// Subscribed after all DATAREFS, 0 if there is no DME signal
+const DME_VALID_DATAREF: &str = "sim/cockpit2/radios/indicators/nav1_has_dme";
+
+/// X-Plane 11 and 12 through their UDP interface
+/// Subscribes to datarefs with RREF and sets them with DREF
+pub struct XPlaneBackend {
+    address: SocketAddr,
+    socket: Option<UdpSocket>,
+    values: Vec<Option<f32>>, // latest value of each subscription, by index
+    last_received: Option<Instant>,
+}
+
+impl XPlaneBackend {
+    pub fn new(address: SocketAddr) -> XPlaneBackend {
+        XPlaneBackend {
+            address,
+            socket: None,
+            values: vec![None; DATAREFS.len() + 1],
+            last_received: None,
+        }
+    }
+
+    fn subscription_paths() -> impl Iterator<Item = &'static str> {
+        DATAREFS
+            .iter()
+            .map(|(_, path, _)| *path)
+            .chain([DME_VALID_DATAREF])
+    }
+
+    /// Store every value X-Plane sent since the last call, without waiting
+    fn receive_values(&mut self) {
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => return,
+        };
+
+        let mut buffer = [0u8; 2048];
+        loop {
+            match socket.recv(&mut buffer) {
+                Ok(size) => {
+                    for (index, value) in parse_rref_packet(&buffer[..size]) {
+                        if let Some(stored) = self.values.get_mut(index as usize) {
+                            *stored = Some(value);
+                            self.last_received = Some(Instant::now());
+                        }
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => break, // e.g. nothing listening on the port yet, shows up as a timeout
+            }
+        }
+    }
+
+    /// Stop X-Plane from sending to the previous socket
+    fn unsubscribe(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            for (index, path) in XPlaneBackend::subscription_paths().enumerate() {
+                let _ = socket.send_to(&rref_packet(0, index as i32, path), self.address);
+            }
+        }
+    }
+
+    fn is_receiving(&self) -> bool {
+        self.last_received
+            .is_some_and(|last_received| last_received.elapsed() < RECEIVE_TIMEOUT)
+    }
+}
+
+impl SimulatorBackend for XPlaneBackend {
+    fn connect(&mut self) -> bool {
+        self.unsubscribe();
+
+        let socket = match UdpSocket::bind("0.0.0.0:0") {
+            Ok(socket) => socket,
+            Err(_) => return false,
+        };
+        for (index, path) in XPlaneBackend::subscription_paths().enumerate() {
+            let packet = rref_packet(RREF_FREQUENCY, index as i32, path);
+            if socket.send_to(&packet, self.address).is_err() {
+                return false;
+            }
+        }
+        if socket.set_nonblocking(true).is_err() {
+            return false;
+        }
+
+        self.socket = Some(socket);
+        self.values = vec![None; DATAREFS.len() + 1];
+        self.last_received = None;
+
+        // Only connected once X-Plane answers
+        let started = Instant::now();
+        while started.elapsed() < CONNECT_TIMEOUT {
+            self.receive_values();
+            if self.is_receiving() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    fn send(&mut self, event: SimEvent) -> bool {
+        self.receive_values();
+
+        let packet = match event {
+            SimEvent::SwapFrequencies(radio) => cmnd_packet(swap_command(radio)),
+            SimEvent::TransponderIdent => cmnd_packet("sim/transponder/transponder_ident"),
+            SimEvent::ToggleAutopilot(mode) => cmnd_packet(autopilot_command(mode)),
+            SimEvent::TransponderMode(mode) => dref_packet(
+                "sim/cockpit2/radios/actuators/transponder_mode",
+                f32::from(xplane_transponder_mode(mode)),
+            ),
+            _ => {
+                let (variable, value) = event.variable_value().expect("sets a dataref");
+                match DATAREFS
+                    .iter()
+                    .find(|(dataref_variable, _, _)| *dataref_variable == variable)
+                {
+                    Some((_, path, factor)) => dref_packet(path, (value / factor) as f32),
+                    None => return self.is_receiving(),
+                }
+            }
+        };
+
+        let sent = match &self.socket {
+            Some(socket) => socket.send_to(&packet, self.address).is_ok(),
+            None => false,
+        };
+        sent && self.is_receiving()
+    }
+
+    fn read_variable(&mut self, variable: SimVariable) -> Option<f64> {
+        self.receive_values();
+
+        if variable == SimVariable::DmeDistance && self.values[DATAREFS.len()]? == 0.0 {
+            return None;
+        }
+
+        let index = DATAREFS
+            .iter()
+            .position(|(dataref_variable, _, _)| *dataref_variable == variable)?;
+        let (_, _, factor) = DATAREFS[index];
+        let value = f64::from(self.values[index]?) * factor;
+        if variable == SimVariable::TransponderMode {
+            return TransponderMode::ALL
+                .into_iter()
+                .find(|mode| f64::from(xplane_transponder_mode(*mode)) == value)
+                .map(|mode| f64::from(mode as u8));
+        }
+        Some(value)
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.receive_values();
+        self.is_receiving()
+    }
+}
+
+/// Ask X-Plane to send a dataref `frequency` times a second, tagged with index
+/// A frequency of 0 stops it
+fn rref_packet(frequency: i32, index: i32, path: &str) -> Vec<u8> {
+    let mut packet = b"RREF\0".to_vec();
+    packet.extend_from_slice(&frequency.to_le_bytes());
+    packet.extend_from_slice(&index.to_le_bytes());
+    packet.extend_from_slice(&null_padded(path, RREF_PATH_SIZE));
+    packet
+}
+
+/// Set a dataref to a value
+fn dref_packet(path: &str, value: f32) -> Vec<u8> {
+    let mut packet = b"DREF\0".to_vec();
+    packet.extend_from_slice(&value.to_le_bytes());
+    packet.extend_from_slice(&null_padded(path, DREF_PATH_SIZE));
+    packet
+}
+
+/// Run an X-Plane command once, like pressing the button it is bound to
+fn cmnd_packet(command: &str) -> Vec<u8> {
+    let mut packet = b"CMND\0".to_vec();
+    packet.extend_from_slice(command.as_bytes());
+    packet
+}
+
+/// The command behind the flip button of a radio
+fn swap_command(radio: Radio) -> &'static str {
+    match radio {
+        Radio::Com1 => "sim/radios/com1_standy_flip",
+        Radio::Com2 => "sim/radios/com2_standy_flip",
+        Radio::Nav1 => "sim/radios/nav1_standy_flip",
+        Radio::Nav2 => "sim/radios/nav2_standy_flip",
+        Radio::Adf => "sim/radios/adf1_standy_flip",
+    }
+}
+
+/// The command behind the button of an autopilot mode
+fn autopilot_command(mode: AutopilotMode) -> &'static str {
+    match mode {
+        AutopilotMode::Master => "sim/autopilot/servos_toggle",
+        AutopilotMode::Heading => "sim/autopilot/heading",
+        AutopilotMode::Altitude => "sim/autopilot/altitude_hold",
+        AutopilotMode::VerticalSpeed => "sim/autopilot/vertical_speed",
+        AutopilotMode::Airspeed => "sim/autopilot/autothrottle_toggle",
+    }
+}
+
+/// X-Plane numbers the mode switch without gaps: OFF, STBY, ON, ALT
+/// Higher values are TEST and GND on some aircraft
+fn xplane_transponder_mode(mode: TransponderMode) -> u8 {
+    match mode {
+        TransponderMode::Off => 0,
+        TransponderMode::Standby => 1,
+        TransponderMode::On => 2,
+        TransponderMode::Altitude => 3,
+    }
+}
+
+fn null_padded(path: &str, size: usize) -> Vec<u8> {
+    let mut padded = path.as_bytes().to_vec();
+    padded.resize(size, 0);
+    padded
+}
+
+/// Returns (index, value) pairs of an RREF answer, nothing for any other packet
+fn parse_rref_packet(packet: &[u8]) -> Vec<(i32, f32)> {
+    if packet.len() < 5 || &packet[..4] != b"RREF" {
+        return Vec::new();
+    }
+
+    packet[5..]
+        .chunks_exact(8)
+        .map(|chunk| {
+            let index = i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            let value = f32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            (index, value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod xplane_tests {
+    use super::*;
+    use std::thread;
+
+    fn rref_answer(values: &[(i32, f32)]) -> Vec<u8> {
+        let mut packet = b"RREF,".to_vec();
+        for (index, value) in values {
+            packet.extend_from_slice(&index.to_le_bytes());
+            packet.extend_from_slice(&value.to_le_bytes());
+        }
+        packet
+    }
+
+    fn index_of(variable: SimVariable) -> i32 {
+        DATAREFS
+            .iter()
+            .position(|(dataref_variable, _, _)| *dataref_variable == variable)
+            .unwrap() as i32
+    }
+
+    #[test]
+    fn test_packets() {
+        let packet = rref_packet(10, 3, "sim/cockpit2/radios/actuators/transponder_code");
+        assert_eq!(packet.len(), 413);
+        assert_eq!(&packet[..5], b"RREF\0");
+        assert_eq!(packet[5..9], 10i32.to_le_bytes());
+        assert_eq!(packet[9..13], 3i32.to_le_bytes());
+        assert!(packet[13..].starts_with(b"sim/cockpit2/radios/actuators/transponder_code\0"));
+
+        let packet = dref_packet("sim/cockpit2/autopilot/altitude_dial_ft", 5000.0);
+        assert_eq!(packet.len(), 509);
+        assert_eq!(packet[5..9], 5000f32.to_le_bytes());
+
+        assert_eq!(
+            cmnd_packet(swap_command(Radio::Nav2)),
+            b"CMND\0sim/radios/nav2_standy_flip"
+        );
+        assert_eq!(xplane_transponder_mode(TransponderMode::Altitude), 3);
+        assert_eq!(
+            cmnd_packet(autopilot_command(AutopilotMode::Heading)),
+            b"CMND\0sim/autopilot/heading"
+        );
+
+        assert_eq!(
+            parse_rref_packet(&rref_answer(&[(1, 2.5), (7, -3.0)])),
+            vec![(1, 2.5), (7, -3.0)]
+        );
+        assert!(parse_rref_packet(b"DATA*").is_empty());
+    }
+
+    #[test]
+    fn test_against_local_xplane() {
+        let xplane = UdpSocket::bind("127.0.0.1:0").unwrap();
+        xplane
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut backend = XPlaneBackend::new(xplane.local_addr().unwrap());
+
+        let stand_in = thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            let mut subscriptions = Vec::new();
+            let mut client = None;
+            for _ in 0..=DATAREFS.len() {
+                let (size, from) = xplane.recv_from(&mut buffer).unwrap();
+                subscriptions.push(buffer[..size].to_vec());
+                client = Some(from);
+            }
+            let client = client.unwrap();
+
+            let com1 = index_of(SimVariable::ActiveFrequency(Radio::Com1));
+            let nav2 = index_of(SimVariable::StandbyFrequency(Radio::Nav2));
+            let dme = index_of(SimVariable::DmeDistance);
+            let mode = index_of(SimVariable::TransponderMode);
+            let has_dme = DATAREFS.len() as i32;
+            let answer = rref_answer(&[
+                (com1, 118_250.0),
+                (mode, 2.0),
+                (nav2, 11_030.0),
+                (dme, 12.5),
+                (has_dme, 1.0),
+            ]);
+            xplane.send_to(&answer, client).unwrap();
+
+            let (size, _) = xplane.recv_from(&mut buffer).unwrap();
+            (subscriptions, buffer[..size].to_vec())
+        });
+
+        assert!(backend.connect());
+        assert_eq!(
+            backend.read_variable(SimVariable::ActiveFrequency(Radio::Com1)),
+            Some(118_250_000.0)
+        );
+        assert_eq!(
+            backend.read_variable(SimVariable::StandbyFrequency(Radio::Nav2)),
+            Some(110_300_000.0)
+        );
+        assert_eq!(backend.read_variable(SimVariable::DmeDistance), Some(12.5));
+        assert_eq!(
+            backend.read_variable(SimVariable::TransponderMode),
+            Some(f64::from(TransponderMode::On as u8))
+        );
+        assert_eq!(backend.read_variable(SimVariable::TransponderCode), None); // not received yet
+
+        assert!(backend.send(SimEvent::TransponderCode(7000)));
+
+        let (subscriptions, dref) = stand_in.join().unwrap();
+        assert!(subscriptions
+            .iter()
+            .all(|packet| packet.starts_with(b"RREF\0")));
+        assert_eq!(
+            dref,
+            dref_packet("sim/cockpit2/radios/actuators/transponder_code", 7000.0)
+        );
+    }
+
+    #[test]
+    fn test_no_xplane_running() {
+        // bound, but never answers
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut backend = XPlaneBackend::new(silent.local_addr().unwrap());
+        assert!(!backend.connect());
+        assert!(!backend.send(SimEvent::TransponderCode(7000)));
+    }
+}